@@ -0,0 +1,286 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{read_storage, remove_storage, write_storage};
+
+// Key the credentials entered in the login prompt are stored under.
+const STORAGE_KEY: &str = "auth.json";
+
+/// Credentials for a backend that requires authentication.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AuthConfig {
+    /// API token, sent as bearer token.
+    Bearer { token: String },
+    /// User name and password.
+    Basic { username: String, password: String },
+    /// User name and password, base64 encoded as for the `Authorization` header.
+    ///
+    /// Base64 is an encoding, not a protection. The password can be read from the credentials.
+    BasicEncoded { credentials: String },
+    /// Custom header, e.g. for a reverse proxy in front of the backend.
+    Header { name: String, value: String },
+}
+
+impl AuthConfig {
+    fn basic_credentials(username: &str, password: &str) -> String {
+        STANDARD.encode(format!("{}:{}", username, password))
+    }
+
+    /// Name and value of the header sent with each request.
+    pub fn header(&self) -> (String, String) {
+        match self {
+            AuthConfig::Bearer { token } => {
+                ("Authorization".to_string(), format!("Bearer {}", token))
+            }
+            AuthConfig::Basic { username, password } => (
+                "Authorization".to_string(),
+                format!("Basic {}", Self::basic_credentials(username, password)),
+            ),
+            AuthConfig::BasicEncoded { credentials } => (
+                "Authorization".to_string(),
+                format!("Basic {}", credentials),
+            ),
+            AuthConfig::Header { name, value } => (name.to_string(), value.to_string()),
+        }
+    }
+
+    /// Value of the websocket's `accessToken` query parameter.
+    ///
+    /// Browsers can't send headers when opening a websocket, so the backend accepts an API token
+    /// or encoded basic credentials as query parameter. Custom headers can't be passed that way.
+    pub fn access_token(&self) -> Option<String> {
+        match self {
+            AuthConfig::Bearer { token } => Some(token.to_string()),
+            AuthConfig::Basic { username, password } => {
+                Some(Self::basic_credentials(username, password))
+            }
+            AuthConfig::BasicEncoded { credentials } => Some(credentials.to_string()),
+            AuthConfig::Header { .. } => None,
+        }
+    }
+}
+
+/// Input of the login prompt.
+#[derive(Default)]
+struct LoginInput {
+    use_token: bool,
+    token: String,
+    username: String,
+    password: String,
+}
+
+/// Authentication against the backend, applied to all requests and the websocket.
+///
+/// Native builds take the credentials from the configuration. In the browser, they are entered
+/// in a login prompt and kept in local storage, so they don't end up in a file that is served.
+#[derive(Default, Resource)]
+pub(crate) struct Auth {
+    /// Credentials, `None` if the backend doesn't require any.
+    pub(crate) config: Option<AuthConfig>,
+    /// Set once the credentials are known. Nothing is requested from the backend before.
+    pub(crate) ready: bool,
+    /// Whether credentials have been looked up in local storage.
+    loaded: bool,
+    /// Set when logged out, the app is restarted with the new credentials.
+    logged_out: bool,
+    login: LoginInput,
+}
+
+/// Restart the app by reloading the page.
+#[cfg(target_arch = "wasm32")]
+fn reload() {
+    if let Some(Err(e)) = web_sys::window().map(|window| window.location().reload()) {
+        error!("Failed to reload: {:?}", e);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn reload() {}
+
+/// Whether the backend rejected a request for missing or wrong credentials.
+///
+/// Requests that are forbidden with valid credentials, e.g. commands for an item the user may
+/// not change, are normal errors.
+pub(crate) fn is_rejected(status: u16) -> bool {
+    status == 401
+}
+
+impl Auth {
+    /// Add the credentials to the given request.
+    pub(crate) fn authorize(&self, request: &mut ehttp::Request) {
+        if let Some(config) = &self.config {
+            let (name, value) = config.header();
+            request.headers.insert(name, value);
+        }
+    }
+
+    /// GET request for the given URL, with credentials.
+    pub(crate) fn get(&self, url: &str) -> ehttp::Request {
+        let mut request = ehttp::Request::get(url);
+        self.authorize(&mut request);
+        request
+    }
+
+    /// URL of the websocket, with credentials.
+    pub(crate) fn websocket_url(&self, url: &str) -> Result<url::Url, url::ParseError> {
+        let mut url = url::Url::parse(url)?;
+        match self.config.as_ref().map(|config| config.access_token()) {
+            Some(Some(token)) => {
+                url.query_pairs_mut().append_pair("accessToken", &token);
+            }
+            Some(None) => warn!("Custom headers can't be passed to the websocket"),
+            None => {}
+        }
+        Ok(url)
+    }
+
+    fn login(&mut self, config: Option<AuthConfig>) {
+        match serde_json::to_string(&config) {
+            Ok(data) => {
+                if let Err(e) = write_storage(STORAGE_KEY, &data) {
+                    error!("Failed to store credentials: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to serialize credentials: {:?}", e),
+        }
+        self.config = config;
+        self.ready = true;
+        self.login = LoginInput::default();
+
+        // Reconnect and request all states with the new credentials
+        if self.logged_out {
+            reload();
+        }
+    }
+
+    /// Forget the credentials and ask for new ones, e.g. if the backend rejected them.
+    ///
+    /// Nothing is requested from the backend until the user logged in again. Native builds use
+    /// the configured credentials, so there is nothing to ask for.
+    pub(crate) fn logout(&mut self) {
+        if !cfg!(target_arch = "wasm32") {
+            error!("The backend rejected the configured credentials");
+            return;
+        }
+        if self.ready {
+            info!("Logging out");
+        }
+        if let Err(e) = remove_storage(STORAGE_KEY) {
+            error!("Failed to remove credentials: {}", e);
+        }
+        self.config = None;
+        self.ready = false;
+        self.logged_out = true;
+    }
+}
+
+#[test]
+fn test_auth() {
+    let basic = AuthConfig::Basic {
+        username: "user".to_string(),
+        password: "pass".to_string(),
+    };
+    assert_eq!(
+        basic.header(),
+        (
+            "Authorization".to_string(),
+            "Basic dXNlcjpwYXNz".to_string()
+        )
+    );
+
+    let encoded = AuthConfig::BasicEncoded {
+        credentials: AuthConfig::basic_credentials("user", "pass"),
+    };
+    assert_eq!(encoded.header(), basic.header());
+
+    // Only missing or wrong credentials log out
+    assert!(is_rejected(401));
+    assert!(!is_rejected(403));
+
+    let auth = Auth {
+        config: Some(AuthConfig::Bearer {
+            token: "oh.token+1".to_string(),
+        }),
+        ..Default::default()
+    };
+    assert_eq!(
+        auth.websocket_url("ws://openhab:8080/ws?topic=smarthome/items/*/*")
+            .map(|url| url.to_string()),
+        Ok("ws://openhab:8080/ws?topic=smarthome/items/*/*&accessToken=oh.token%2B1".to_string())
+    );
+}
+
+/// Whether the user has to log in, which is only done in the browser.
+pub(crate) fn needs_login(auth: Res<Auth>) -> bool {
+    cfg!(target_arch = "wasm32") && !auth.ready
+}
+
+/// Button to log out, shown in the browser once logged in.
+pub(crate) fn logout_button(ui: &mut egui::Ui, auth: &mut Auth) {
+    if cfg!(target_arch = "wasm32") && auth.ready && ui.button("Log out").clicked() {
+        auth.logout();
+    }
+}
+
+/// Ask for the backend's credentials, unless they have been stored before.
+pub(crate) fn login_prompt(mut contexts: EguiContexts, mut auth: ResMut<Auth>) {
+    if !auth.loaded {
+        auth.loaded = true;
+        let stored = read_storage(STORAGE_KEY)
+            .and_then(|data| serde_json::from_str::<Option<AuthConfig>>(&data).ok());
+        if let Some(config) = stored {
+            auth.config = config;
+            auth.ready = true;
+            return;
+        }
+    }
+
+    let mut submitted = None;
+    egui::Window::new("Login")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let login = &mut auth.login;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut login.use_token, true, "API token");
+                ui.radio_value(&mut login.use_token, false, "User and password");
+            });
+            if login.use_token {
+                ui.add(egui::TextEdit::singleline(&mut login.token).password(true));
+            } else {
+                ui.add(egui::TextEdit::singleline(&mut login.username).hint_text("User"));
+                ui.add(
+                    egui::TextEdit::singleline(&mut login.password)
+                        .password(true)
+                        .hint_text("Password"),
+                );
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Log in").clicked() {
+                    submitted = Some(Some(match login.use_token {
+                        true => AuthConfig::Bearer {
+                            token: login.token.clone(),
+                        },
+                        // Stored as sent to the backend. Anyone with access to the local
+                        // storage can decode the password, like they can read a token.
+                        false => AuthConfig::BasicEncoded {
+                            credentials: AuthConfig::basic_credentials(
+                                &login.username,
+                                &login.password,
+                            ),
+                        },
+                    }));
+                }
+                if ui.button("Continue without login").clicked() {
+                    submitted = Some(None);
+                }
+            });
+        });
+
+    if let Some(config) = submitted {
+        auth.login(config);
+    }
+}
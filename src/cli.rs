@@ -0,0 +1,56 @@
+use std::fs;
+
+use crate::{import, schema};
+
+const USAGE: &str = "Usage: nextnext [COMMAND]
+
+Without a command, the visualization is started.
+
+Commands:
+  schema [OUTPUT]         Write the JSON Schema of items.json to OUTPUT, or to stdout
+  import SOURCE [OUTPUT]  Generate a starter items.json from openHAB and write it to OUTPUT,
+                          or to stdout. SOURCE is the URL of openHAB, e.g.
+                          http://openhab:8080, a .sitemap file or a file with the response
+                          of /rest/items?metadata=semantics. An API token for openHAB can be
+                          given in the environment variable OPENHAB_TOKEN.";
+
+/// Run the subcommand given on the command line, for tooling around `items.json`.
+///
+/// Returns `None` if no subcommand was given and the app should be started, otherwise the exit
+/// code.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+    let result = match command.as_str() {
+        "schema" => write_schema(args.first().map(|s| s.as_str())),
+        "import" => match args.first() {
+            Some(source) => write_import(source, args.get(1).map(|s| s.as_str())),
+            None => Err(format!("Missing source to import from\n\n{}", USAGE)),
+        },
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command {}\n\n{}", command, USAGE)),
+    };
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
+
+fn write_schema(output: Option<&str>) -> Result<(), String> {
+    let schema = serde_json::to_string_pretty(&schema::config_schema())
+        .map_err(|e| format!("Failed to serialize schema: {}", e))?;
+    match output {
+        Some(path) => {
+            fs::write(path, schema).map_err(|e| format!("Failed to write {}: {}", path, e))
+        }
+        None => {
+            println!("{}", schema);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use ehttp::{Headers, Request, Response};
+
+use crate::auth::{self, Auth};
+use crate::config::RetryPolicy;
+use crate::device_model::DeviceModel;
+use crate::history;
+use crate::openhab::RequestedStateChange;
+use crate::toasts::Toasts;
+use crate::OPENHAB_IP;
+
+type CommandId = u64;
+
+/// A state change sent to the backend.
+struct Command {
+    change: RequestedStateChange,
+    /// Number of retries so far.
+    retries: u32,
+    /// Time of the next retry, as seconds since the epoch.
+    retry_at: f64,
+    /// Whether a newer command for the same item was sent, so the result of this one is ignored.
+    superseded: bool,
+}
+
+/// Commands sent to the backend, until it responded to them.
+///
+/// Each command is sent with its own callback, so responses and transport errors can be related
+/// to the command and the widget that requested it.
+#[derive(Default, Resource)]
+pub(crate) struct CommandQueue {
+    pub(crate) retry_policy: RetryPolicy,
+    next_id: CommandId,
+    in_flight: HashMap<CommandId, Command>,
+    retries: Vec<Command>,
+    /// Results passed back by the callbacks of the requests.
+    results: Arc<Mutex<Vec<(CommandId, Result<Response, String>)>>>,
+}
+
+/// Whether a failed command should be retried.
+///
+/// Requests rejected by the backend, e.g. for an unknown item or invalid value, will fail again.
+fn is_retryable(result: &Result<Response, String>) -> bool {
+    match result {
+        Ok(response) => response.status >= 500,
+        Err(_) => true,
+    }
+}
+
+impl CommandQueue {
+    fn send(&mut self, command: Command, auth: &Auth) {
+        let mut request = Request {
+            method: "POST".to_string(),
+            url: format!(
+                "http://{}:8080/rest/items/{}",
+                OPENHAB_IP, command.change.item
+            ),
+            body: command.change.value.as_bytes().to_vec(),
+            headers: Headers::new(&[("Content-Type", "text/plain")]),
+            #[cfg(target_arch = "wasm32")]
+            mode: ehttp::Mode::Cors,
+        };
+        auth.authorize(&mut request);
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(id, command);
+
+        let results = self.results.clone();
+        ehttp::fetch(request, move |result| {
+            if let Ok(mut results) = results.lock() {
+                results.push((id, result));
+            }
+        });
+    }
+
+    /// A new command for the given item replaces the older ones.
+    ///
+    /// Queued retries are dropped and results of commands still in flight are ignored, so they
+    /// can't overwrite the new state or fail its pending change.
+    fn supersede(&mut self, item_name: &str) {
+        self.retries
+            .retain(|command| command.change.item != item_name);
+        for command in self.in_flight.values_mut() {
+            if command.change.item == item_name {
+                command.superseded = true;
+            }
+        }
+    }
+
+    /// Results of the commands the backend responded to, except superseded ones.
+    fn take_results(&mut self) -> Vec<(Command, Result<Response, String>)> {
+        let results = match self.results.lock() {
+            Ok(mut results) => std::mem::take(&mut *results),
+            Err(_) => vec![],
+        };
+        results
+            .into_iter()
+            .filter_map(|(id, result)| Some((self.in_flight.remove(&id)?, result)))
+            .filter(|(command, _)| !command.superseded)
+            .collect()
+    }
+}
+
+/// Send state changes requested by widgets or the 3D scene to the backend.
+pub(crate) fn send_commands(
+    mut ev_state_change: EventReader<RequestedStateChange>,
+    mut queue: ResMut<CommandQueue>,
+    auth: Res<Auth>,
+) {
+    for change in ev_state_change.read() {
+        queue.supersede(&change.item);
+        queue.send(
+            Command {
+                change: change.clone(),
+                retries: 0,
+                retry_at: 0.,
+                superseded: false,
+            },
+            &auth,
+        );
+    }
+
+    let now = history::now();
+    let (due, waiting): (Vec<Command>, Vec<Command>) = std::mem::take(&mut queue.retries)
+        .into_iter()
+        .partition(|command| command.retry_at <= now);
+    queue.retries = waiting;
+    for command in due {
+        queue.send(command, &auth);
+    }
+}
+
+/// Handle the backend's responses to commands.
+///
+/// Successful commands are confirmed by the backend's state updates. Failed commands are retried
+/// according to the retry policy, after that the widget is reset and the error is shown. If the
+/// backend rejected the credentials, the user is logged out.
+pub(crate) fn handle_command_results(
+    mut queue: ResMut<CommandQueue>,
+    mut device_model: ResMut<DeviceModel>,
+    mut toasts: ResMut<Toasts>,
+    mut auth: ResMut<Auth>,
+) {
+    for (mut command, result) in queue.take_results() {
+        let reason = match &result {
+            Ok(response) if response.ok => continue,
+            Ok(response) => {
+                if auth::is_rejected(response.status) {
+                    auth.logout();
+                }
+                format!("{} {}", response.status, response.status_text)
+            }
+            Err(e) => e.to_string(),
+        };
+
+        let change = &command.change;
+        match queue.retry_policy.delay(command.retries) {
+            Some(delay) if is_retryable(&result) => {
+                warn!(
+                    "Setting {} to {} failed ({}), retrying in {} s",
+                    change.item, change.value, reason, delay
+                );
+                command.retries += 1;
+                command.retry_at = history::now() + delay;
+                device_model.command_retrying(&change.item, command.retry_at);
+                queue.retries.push(command);
+            }
+            _ => {
+                device_model.command_failed(&change.item, &reason);
+                toasts.error(&format!(
+                    "{}: setting {} to {} failed: {}",
+                    device_model.widget_label(&change.widget),
+                    change.item,
+                    change.value,
+                    reason
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_superseded_commands() {
+    let command = |item: &str, value: &str| Command {
+        change: RequestedStateChange {
+            item: item.to_string(),
+            value: value.to_string(),
+            widget: item.to_string(),
+        },
+        retries: 1,
+        retry_at: 0.,
+        superseded: false,
+    };
+    let mut queue = CommandQueue::default();
+    queue.retries.push(command("Light", "ON"));
+    queue.retries.push(command("Fan", "ON"));
+    queue.in_flight.insert(0, command("Light", "100"));
+    queue.in_flight.insert(1, command("Fan", "OFF"));
+
+    queue.supersede("Light");
+    assert_eq!(queue.retries.len(), 1);
+    assert_eq!(queue.retries[0].change.item, "Fan");
+
+    // The late result of the older command is ignored
+    if let Ok(mut results) = queue.results.lock() {
+        results.push((0, Err("timeout".to_string())));
+        results.push((1, Err("timeout".to_string())));
+    }
+    let results = queue.take_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0.change.item, "Fan");
+}
@@ -1,11 +1,50 @@
 use std::collections::HashMap;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+use crate::auth::AuthConfig;
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct ItemConfiguration {
     pub views: HashMap<String, ViewConfiguration>,
     pub zooms: HashMap<String, HashMap<String, (f64, f64, f64, f64)>>,
+    /// Retrying commands the backend failed to process.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Credentials for the backend, only used by native builds.
+    pub auth: Option<AuthConfig>,
+}
+
+/// Policy for retrying failed commands.
+///
+/// Commands are retried on transport errors and server errors, but not if the backend rejected
+/// them, e.g. because of an invalid value.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Seconds before the first retry.
+    pub delay: f64,
+    /// Factor the delay grows by with each retry.
+    pub backoff: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 2,
+            delay: 1.,
+            backoff: 2.,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Seconds to wait before the given retry, starting at 0, or `None` if it's not retried.
+    pub fn delay(&self, retry: u32) -> Option<f64> {
+        (retry < self.max_retries).then(|| self.delay * self.backoff.powi(retry as i32))
+    }
 }
 
 impl ItemConfiguration {
@@ -25,9 +64,40 @@ impl ItemConfiguration {
         }
         None
     }
+
+    /// Return the configuration of the given widget, if it exists.
+    pub fn widget(&self, widget_name: &str) -> Option<&Item> {
+        self.views
+            .values()
+            .find_map(|view_configuration| view_configuration.items.get(widget_name))
+    }
+
+    pub fn widget_mut(&mut self, widget_name: &str) -> Option<&mut Item> {
+        self.views
+            .values_mut()
+            .find_map(|view_configuration| view_configuration.items.get_mut(widget_name))
+    }
+
+    /// Serialize as pretty printed JSON, with sorted keys and without unset optional fields.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        fn strip_nulls(value: &mut serde_json::Value) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    map.retain(|_, value| !value.is_null());
+                    map.values_mut().for_each(strip_nulls);
+                }
+                serde_json::Value::Array(values) => values.iter_mut().for_each(strip_nulls),
+                _ => {}
+            }
+        }
+
+        let mut value = serde_json::to_value(self)?;
+        strip_nulls(&mut value);
+        serde_json::to_string_pretty(&value)
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ViewConfiguration {
     pub image: Option<String>,
@@ -51,7 +121,7 @@ pub struct SmartHomeItemInternal {
 
 /// Internal representation of a smart home item.
 /// Needs to have a key set.
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SmartHomeItem {
     pub key: Option<String>,
@@ -72,7 +142,7 @@ pub fn smart_home_item_to_internal(
     map
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Item {
     pub blender_items: HashMap<String, Vec<String>>,
@@ -85,6 +155,8 @@ pub struct Item {
     pub smarthome_items: HashMap<String, SmartHomeItem>,
     pub label: Option<String>,
     pub show_mobile: Option<bool>,
+    /// The room this widget is in. Used to group notifications.
+    pub room: Option<String>,
 }
 
 impl Item {
@@ -107,3 +179,11 @@ pub struct View {
     pub ui_elements: Vec<String>, // These are widget_names, key to item_list
     pub order: i32,
 }
+
+#[test]
+fn test_retry_policy() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.delay(0), Some(1.));
+    assert_eq!(policy.delay(1), Some(2.));
+    assert_eq!(policy.delay(2), None);
+}
@@ -1,16 +1,35 @@
 use bevy::prelude::*;
 use bevy_egui::egui;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::config::ItemConfiguration;
-use crate::errors::DeviceModelError;
+use crate::errors::{ConfigError, ConfigErrorKind, DeviceModelError};
 use crate::events::SceneModificationEvent;
-use crate::openhab::{self, RequestedStateChange};
+use crate::history::{self, History, HistoryRange};
+use crate::item::generic_item::INTERNAL_STATE_PREFIX;
+use crate::notifications::Notification;
+use crate::openhab::{self, RequestedStateChange, RequestedStateChangeFromWidget};
+use crate::pending::PendingChanges;
 use crate::widget_settings::*;
 
 use crate::config::smart_home_item_to_internal;
 use crate::item;
-use crate::item::Item;
+use crate::item::{ChangeStatus, Item};
+
+/// State of items that never reported one, same as the backend reports it.
+const NULL_STATE: &str = "NULL";
+
+/// Name of the file (native) or local storage key (wasm) the state kept by widgets is stored in.
+const WIDGET_STATE_KEY: &str = "widgets.json";
+/// Range of the persistence data requested for widgets learning from past values.
+const LEARNING_RANGE: HistoryRange = HistoryRange::Week;
+
+/// Interactions with the 3D scene that are forwarded to the widget owning the picked entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SceneInteraction {
+    Click,
+    LongPress,
+}
 
 #[derive(Default, Resource)]
 pub struct DeviceModel {
@@ -38,42 +57,107 @@ pub struct DeviceModel {
 
     /// Widget which is in fullscreen mode, if any.
     pub(crate) fullscreen_widget: Option<WidgetName>,
+
+    /// History of item states, plotted in the fullscreen view.
+    pub(crate) history: History,
+
+    /// Widget currently previewing changes in the 3D scene, if any.
+    preview_widget: Option<WidgetName>,
+
+    /// Widgets rendered in the last frame, previews of other widgets are ended.
+    rendered_widgets: HashSet<WidgetName>,
+
+    /// Scene modifications not triggered by the backend, e.g. by previews.
+    scene_changes: Vec<SceneModificationEvent>,
+
+    /// State changes shown by the widgets, but not yet confirmed by the backend.
+    pending: PendingChanges,
+
+    /// Configuration the widgets have been built from, to detect changes when reloading.
+    configuration: Option<ItemConfiguration>,
+
+    /// Errors in the configuration of widgets that could not be built.
+    widget_errors: HashMap<WidgetName, Vec<ConfigError>>,
+}
+
+/// Widget types supported in `itemType`.
+///
+/// `DeviceModel::init_widget()` only instantiates these, and the configuration schema lists
+/// them, so both can't get out of sync.
+pub const ITEM_TYPES: &[&str] = &[
+    "Color",
+    "Switch",
+    "Dimmer",
+    "DimmerGroup",
+    "Number",
+    "Text",
+    "Calendar",
+    "Climate",
+    "Music",
+    "Scene",
+    "Car",
+    "Robot",
+    "EnergyMonitor",
+    "Laundry",
+    "Appliance",
+    "LightAuto",
+];
+
+/// Widgets of the configuration with their view, by widget name.
+///
+/// Views can share widgets by using the same widget name. A shared widget is built from its
+/// definition in the first view, in alphabetical order.
+fn widget_configs(
+    configuration: &ItemConfiguration,
+) -> HashMap<WidgetName, (ViewName, crate::config::Item)> {
+    let mut view_names = configuration.views.keys().collect::<Vec<_>>();
+    view_names.sort();
+
+    let mut widgets: HashMap<WidgetName, (ViewName, crate::config::Item)> = HashMap::new();
+    for view_name in view_names {
+        for (widget_name, widget_config) in &configuration.views[view_name].items {
+            widgets
+                .entry(widget_name.to_string())
+                .or_insert_with(|| (view_name.to_string(), widget_config.clone()));
+        }
+    }
+    widgets
 }
 
 impl DeviceModel {
     /// Instantiate widget from widget type and configuration.
-    fn init_widget(
+    ///
+    /// Returns `None` for unknown widget types, which are the ones missing in `ITEM_TYPES`.
+    pub(crate) fn init_widget(
         widget_type: &str,
         widget_config: &crate::config::Item,
-    ) -> Box<dyn Item + Send + Sync> {
-        match widget_type {
+    ) -> Option<Box<dyn Item + Send + Sync>> {
+        if !ITEM_TYPES.contains(&widget_type) {
+            return None;
+        }
+        let widget: Box<dyn Item + Send + Sync> = match widget_type {
             "Color" => Box::new(item::color::Color::new()),
             "Switch" => Box::new(item::switch::Switch::new()),
             "Dimmer" => Box::new(item::dimmer::Dimmer::new(&smart_home_item_to_internal(
                 &widget_config.smarthome_items,
             ))),
             "DimmerGroup" => Box::new(item::dimmer_group::DimmerGroup::default()),
-            // "Number" => Box::new(item::Number::new()),
+            "Number" => Box::new(item::Number::new()),
             // "Blind" => Box::new(item::Blind::new()),
             // "Contact" => Box::new(item::contact::new()),
-            // "Text" => Box::new(item::Text::new()),
-            // "Calendar" => Box::new(item::Calendar::new()),
-            // "Climate" => Box::new(item::climate::Climate::new()),
-            // "Music" => Box::new(item::Music::new()),
-            // "Scene" => Box::new(item::Scene::new()),
-            // "Car" => Box::new(item::car::new()),
-            // "Robot" => Box::new(item::robot::new()),
-            // "EnergyMonitor" => Box::new(item::energy_monitor::new()),
-            // "Laundry" => Box::new(item::laundry::new()),
-            // "LightAuto" => Box::new(item::light_auto::new()),
-            _ => {
-                error!(
-                    "Error in configuration - unknown item type: {}",
-                    widget_type
-                );
-                Box::new(item::Number::new())
-            }
-        }
+            "Text" => Box::new(item::Text::new()),
+            "Calendar" => Box::new(item::Calendar::new()),
+            "Climate" => Box::new(item::climate::Climate::new()),
+            "Music" => Box::new(item::Music::new()),
+            "Scene" => Box::new(item::Scene::new()),
+            "Car" => Box::new(item::car::new()),
+            "Robot" => Box::new(item::robot::new()),
+            "EnergyMonitor" => Box::new(item::energy_monitor::new()),
+            "Laundry" | "Appliance" => Box::new(item::laundry::new()),
+            "LightAuto" => Box::new(item::light_auto::new()),
+            _ => return None,
+        };
+        Some(widget)
     }
 
     /// Build data structure to map from OpenHab name to widget and corresponding key.
@@ -93,22 +177,159 @@ impl DeviceModel {
         // This widget does not have any smart home items explictly configured. Assume item name
         // equals widget name.
         if widget_config.smarthome_items.len() == 0 {
-            // Since we do not have any smart home items for this widget, it should not be in the
-            // hashmap yet. We check the return code of insert() for this.
-            let previous = self.smart_home_items.insert(
-                widget_name.to_string(),
-                vec![(widget_name.to_string(), widget_name.to_string())],
-            );
-            assert!(
-                previous.is_none(),
-                "Duplicate widget name {} - previous value: {:?}, config: {:?}",
-                &widget_name,
-                &previous,
-                &widget_config
-            );
+            self.smart_home_items
+                .entry(widget_name.to_string())
+                .or_insert(Vec::new())
+                .push((widget_name.to_string(), widget_name.to_string()));
+        }
+    }
+
+    /// Instantiate a widget and register its settings and items.
+    ///
+    /// Widgets with errors in their configuration are not added, all errors found are returned.
+    fn add_widget(
+        &mut self,
+        view_name: &str,
+        widget_name: &str,
+        widget_config: &crate::config::Item,
+    ) -> Result<(), Vec<ConfigError>> {
+        let widget_type: &str = &widget_config.item_type;
+        let mut errors = vec![];
+
+        // Get widget instance based on the widget type
+        let mut widget = DeviceModel::init_widget(widget_type, widget_config);
+        if widget.is_none() {
+            errors.push(ConfigError::widget(
+                view_name,
+                widget_name,
+                Some("itemType"),
+                ConfigErrorKind::UnknownItemType(widget_type.to_string()),
+            ));
         }
+
+        // Pass configuration to widget
+        if let (Some(widget), Some(template_conf)) = (&mut widget, &widget_config.template_conf) {
+            if let Err(e) = widget.set_configuration(template_conf) {
+                errors.push(ConfigError::widget(
+                    view_name,
+                    widget_name,
+                    Some("templateConf"),
+                    ConfigErrorKind::InvalidTemplateConf(e.to_string()),
+                ));
+            }
+        }
+
+        let render_settings =
+            WidgetRenderSetting::from_widget_config(view_name, widget_name, widget_config)
+                .map_err(|mut e| errors.append(&mut e));
+        let scene_modifications = WidgetSceneModifications::from_widget_config(
+            view_name,
+            widget_name,
+            &widget_config.blender_items,
+        )
+        .map_err(|mut e| errors.append(&mut e));
+
+        let (Some(mut widget), Ok(render_settings), Ok(scene_modifications)) =
+            (widget, render_settings, scene_modifications)
+        else {
+            return Err(errors);
+        };
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Pass list of smarthome items to widget
+        widget.set_smarthome_items(&smart_home_item_to_internal(&widget_config.smarthome_items));
+
+        // Store reference to widget
+        self.widgets.insert(widget_name.to_string(), widget);
+
+        // Store settings for widgets. Those include information needed for rendering as well
+        // as information for lookup from key to item.
+        self.widget_settings.insert(
+            widget_name.to_string(),
+            WidgetSettings {
+                render_settings,
+                item_list: WidgetItemList::from_widget_config(widget_name, widget_config),
+                scene_modifications,
+                room: widget_config.room.clone(),
+                label: widget_config.label.clone(),
+            },
+        );
+
+        self.parse_smarthome_items_for_widget(widget_name, widget_config);
+        Ok(())
+    }
+
+    /// Add a widget, remembering the errors in its configuration if it can't be added.
+    fn add_widget_or_report(
+        &mut self,
+        view_name: &str,
+        widget_name: &str,
+        widget_config: &crate::config::Item,
+    ) -> bool {
+        match self.add_widget(view_name, widget_name, widget_config) {
+            Ok(()) => true,
+            Err(errors) => {
+                for e in &errors {
+                    error!("Error in configuration - {}", e);
+                }
+                self.widget_errors.insert(widget_name.to_string(), errors);
+                false
+            }
+        }
+    }
+
+    /// Drop a widget together with its settings and items.
+    ///
+    /// The changes the widget made to the 3D scene are undone.
+    fn remove_widget(&mut self, widget_name: &str) {
+        self.widgets.remove(widget_name);
+        if let Some(widget_settings) = self.widget_settings.remove(widget_name) {
+            for (entity, modifications) in &widget_settings.scene_modifications.config {
+                for modification in modifications {
+                    self.scene_changes.append(&mut modification.reset(entity));
+                }
+            }
+        }
+        self.widget_errors.remove(widget_name);
+        for widgets in self.smart_home_items.values_mut() {
+            widgets.retain(|(name, _)| name != widget_name);
+        }
+        self.smart_home_items
+            .retain(|_, widgets| !widgets.is_empty());
+        if self.fullscreen_widget.as_deref() == Some(widget_name) {
+            self.fullscreen_widget = None;
+        }
+    }
+
+    /// Remember the widgets to be displayed for each view, skipping the ones with errors.
+    fn set_views(&mut self, configuration: &ItemConfiguration) {
+        self.views = configuration
+            .views
+            .iter()
+            .map(|(view_name, view)| {
+                let widgets_of_view = view
+                    .items
+                    .keys()
+                    .filter(|widget_name| self.widgets.contains_key(*widget_name))
+                    .cloned()
+                    .collect();
+                (view_name.to_string(), widgets_of_view)
+            })
+            .collect();
     }
 
+    /// Problems found in the configuration, sorted by their path.
+    pub(crate) fn config_errors(&self) -> Vec<&ConfigError> {
+        let mut errors = self.widget_errors.values().flatten().collect::<Vec<_>>();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors
+    }
+
+    /// Build all widgets from the configuration.
+    ///
+    /// Widgets with errors in their configuration are skipped, see `config_errors()`.
     pub fn parse(&mut self, configuration: &ItemConfiguration) {
         // XXX - Choose view based on ordering in json ..
         assert!(
@@ -118,54 +339,76 @@ impl DeviceModel {
         self.current_view = Some("floorplan".to_string());
 
         // Parse configuration
-        for (view_name, view) in &configuration.views {
-            let mut widgets_of_view = vec![];
+        for (widget_name, (view_name, widget_config)) in &widget_configs(configuration) {
+            self.add_widget_or_report(view_name, widget_name, widget_config);
+        }
 
-            for (widget_name, widget_config) in &view.items {
-                widgets_of_view.push(widget_name.to_string());
-                let widget_type: &str = &widget_config.item_type;
+        self.set_views(configuration);
+        self.configuration = Some(configuration.clone());
+        self.initialized = true;
+    }
 
-                // Get widget instance based on the widget type
-                let mut widget = DeviceModel::init_widget(widget_type, widget_config);
+    /// Apply a modified configuration.
+    ///
+    /// Only widgets whose configuration changed are rebuilt, the others keep their state. Rebuilt
+    /// widgets get the last state the backend reported for their items.
+    ///
+    /// Returns the items of rebuilt widgets with unknown state, to be requested from the backend.
+    pub(crate) fn reload(&mut self, configuration: &ItemConfiguration) -> Vec<ItemName> {
+        let old = self
+            .configuration
+            .as_ref()
+            .map(widget_configs)
+            .unwrap_or_default();
+        let new = widget_configs(configuration);
 
-                // Pass configuration to widget
-                if let Some(template_conf) = &widget_config.template_conf {
-                    widget.set_configuration(template_conf);
+        // Rebuilt widgets keep the state they would keep across restarts
+        let mut stored_states = HashMap::new();
+        for widget_name in old.keys() {
+            if old.get(widget_name) != new.get(widget_name) {
+                info!("Reload: removing widget {}", widget_name);
+                if let Some(state) = self.widgets.get(widget_name).and_then(|w| w.stored_state()) {
+                    stored_states.insert(widget_name.to_string(), state);
                 }
+                self.remove_widget(widget_name);
+            }
+        }
 
-                // Pass list of smarthome items to widget
-                widget.set_smarthome_items(&smart_home_item_to_internal(
-                    &widget_config.smarthome_items,
-                ));
-
-                // Store reference to widget
-                self.widgets.insert(widget_name.to_string(), widget);
-
-                // Store settings for widgets. Those include information needed for rendering as well
-                // as information for lookup from key to item.
-                self.widget_settings.insert(
-                    widget_name.to_string(),
-                    WidgetSettings {
-                        render_settings: WidgetRenderSetting::from_widget_config(
-                            view_name,
-                            widget_name,
-                            widget_config,
-                        ),
-                        item_list: WidgetItemList::from_widget_config(widget_name, widget_config),
-                        scene_modifications: WidgetSceneModifications::from_widget_config(
-                            &widget_config.blender_items,
-                        ),
-                    },
-                );
-
-                self.parse_smarthome_items_for_widget(widget_name, widget_config);
+        let mut unknown_items = vec![];
+        for (widget_name, widget) in &new {
+            if old.get(widget_name) == Some(widget) {
+                continue;
+            }
+            let (view_name, widget_config) = widget;
+            info!("Reload: building widget {}", widget_name);
+            if !self.add_widget_or_report(view_name, widget_name, widget_config) {
+                continue;
             }
 
-            // Remember all widgets to be displayed for that view
-            self.views.insert(view_name.to_string(), widgets_of_view);
+            let Some(widget) = self.widgets.get_mut(widget_name) else {
+                continue;
+            };
+            if let Some(state) = stored_states.get(widget_name) {
+                widget.restore_state(state);
+            }
+            for (item_name, widgets) in &self.smart_home_items {
+                for (_, key) in widgets.iter().filter(|(name, _)| name == widget_name) {
+                    match self.pending.last_state(item_name) {
+                        Some(state) => widget.initial_state(key, state),
+                        None => unknown_items.push(item_name.to_string()),
+                    }
+                }
+            }
+            match self.scene_modifications(widget_name) {
+                Ok(mut changes) => self.scene_changes.append(&mut changes),
+                Err(e) => error!("Failed to update scene of {}: {:?}", widget_name, e),
+            }
         }
-
-        self.initialized = true;
+        self.set_views(configuration);
+        self.configuration = Some(configuration.clone());
+        unknown_items.sort();
+        unknown_items.dedup();
+        unknown_items
     }
 
     /// Render all widgets for the current view using Egui.
@@ -183,6 +426,10 @@ impl DeviceModel {
         camera_global_transform: &GlobalTransform,
     ) -> Result<Vec<RequestedStateChange>, DeviceModelError> {
         let mut requests = vec![];
+        let mut internal_changes = vec![];
+
+        self.expire_pending_changes();
+        self.rendered_widgets.clear();
 
         // Current view is set
         let current_view = self
@@ -213,16 +460,15 @@ impl DeviceModel {
 
                 // Get viewport position for the given 3D coordinates via camera.
                 // https://github.com/bevyengine/bevy/blob/release-0.13.2/examples/3d/blend_modes.rs
-                let viewport_position = camera.world_to_viewport(
-                    camera_global_transform,
-                    Vec3::new(render_settings.left, 2.0, render_settings.top),
-                );
+                let viewport_position = camera
+                    .world_to_viewport(camera_global_transform, render_settings.world_position());
 
                 let mut widget_requests = vec![];
 
                 // If visible with current camera,
                 // render widget and append HTTP requests triggerd by those widgets
                 if let Some(viewport_position) = viewport_position {
+                    self.rendered_widgets.insert(widget_name.to_string());
                     widget_requests.append(&mut widget.render_egui(
                         (viewport_position.x, viewport_position.y),
                         &render_settings,
@@ -230,33 +476,324 @@ impl DeviceModel {
                     ));
                 }
 
-                // Render fullscreen view, if requested.
-                if self.fullscreen_widget.as_ref() == Some(widget_name) {
-                    info_once!("Rendering fullscreen: {}", widget_name);
-                    widget_requests
-                        .append(&mut widget.render_fullscreen(&render_settings, context));
+                requests.append(&mut DeviceModel::handle_widget_interactions(
+                    &mut self.fullscreen_widget,
+                    &mut internal_changes,
+                    widget_name,
+                    &widget_settings.item_list,
+                    widget_requests,
+                ));
+            }
+        }
+
+        // Render fullscreen view, if requested. The widget doesn't need to be part of the current
+        // view or have a position, e.g. when opened from the slider strip.
+        if let Some(widget_name) = self.fullscreen_widget.clone() {
+            let widget = self
+                .widgets
+                .get(&widget_name)
+                .ok_or(DeviceModelError::WidgetNotFound(widget_name.to_string()))?;
+            let widget_settings = self.widget_settings.get(&widget_name).ok_or(
+                DeviceModelError::WidgetSettingsNotFound(widget_name.to_string()),
+            )?;
+            let without_position;
+            let render_settings = match &widget_settings.render_settings {
+                Some(render_settings) => render_settings,
+                None => {
+                    without_position = WidgetRenderSetting::without_position(
+                        &widget_name,
+                        widget_settings.label.clone(),
+                    );
+                    &without_position
                 }
+            };
+
+            info_once!("Rendering fullscreen: {}", widget_name);
+            self.rendered_widgets.insert(widget_name.to_string());
+            let widget_requests = widget.render_fullscreen(render_settings, context);
 
-                for widget_request in widget_requests {
-                    match widget_request {
-                        openhab::WidgetInteraction::StateChange(state_change) => {
-                            requests.push(RequestedStateChange::from_widget_request(
-                                &state_change,
-                                &widget_settings.item_list,
-                            )?)
+            let series = widget_settings
+                .item_list
+                .items
+                .iter()
+                .map(|(key, item)| (key.to_string(), item.to_string(), widget.get_plot_type(key)))
+                .collect::<Vec<_>>();
+            self.history.render_window(
+                context,
+                render_settings.id,
+                render_settings.label.as_ref().unwrap_or(&widget_name),
+                &series,
+            );
+
+            requests.append(&mut DeviceModel::handle_widget_interactions(
+                &mut self.fullscreen_widget,
+                &mut internal_changes,
+                &widget_name,
+                &widget_settings.item_list,
+                widget_requests,
+            ));
+        }
+
+        self.apply_internal_changes(internal_changes);
+        self.apply_optimistic_changes(&requests);
+        Ok(requests)
+    }
+
+    /// Translate interactions requested by a widget.
+    ///
+    /// State changes are looked up to the corresponding backend items and returned, fullscreen
+    /// requests are applied directly. Changes of internal state (keys starting with
+    /// [`INTERNAL_STATE_PREFIX`]) never reach the backend, they are collected in `internal_changes`
+    /// to be passed back to the widget. State changes for keys missing from the widget's items are
+    /// logged and dropped.
+    fn handle_widget_interactions(
+        fullscreen_widget: &mut Option<WidgetName>,
+        internal_changes: &mut Vec<(WidgetName, RequestedStateChangeFromWidget)>,
+        widget_name: &str,
+        item_list: &WidgetItemList,
+        widget_requests: Vec<openhab::WidgetInteraction>,
+    ) -> Vec<RequestedStateChange> {
+        let mut requests = vec![];
+        for widget_request in widget_requests {
+            match widget_request {
+                openhab::WidgetInteraction::StateChange(state_change)
+                    if state_change.key.starts_with(INTERNAL_STATE_PREFIX) =>
+                {
+                    internal_changes.push((widget_name.to_string(), state_change))
+                }
+                openhab::WidgetInteraction::StateChange(state_change) => {
+                    // A misconfigured widget must not stop the other widgets from rendering.
+                    match RequestedStateChange::from_widget_request(
+                        widget_name,
+                        &state_change,
+                        item_list,
+                    ) {
+                        Ok(request) => requests.push(request),
+                        Err(e) => {
+                            error!("Dropping state change of widget {}: {:?}", widget_name, e)
                         }
-                        openhab::WidgetInteraction::FullscreenRequest(enable) => match enable {
-                            true => self.fullscreen_widget = Some(widget_name.to_string()),
-                            false => self.fullscreen_widget = None,
-                        },
                     }
                 }
+                openhab::WidgetInteraction::FullscreenRequest(enable) => match enable {
+                    true => *fullscreen_widget = Some(widget_name.to_string()),
+                    false => *fullscreen_widget = None,
+                },
+            }
+        }
+        requests
+    }
+
+    /// Pass changes of internal state back to the widgets that requested them.
+    ///
+    /// Widgets may preview changes in the 3D scene depending on their internal state. Once a
+    /// preview ends, the scene is restored from the state of all widgets. Previews of widgets that
+    /// were not rendered in the last frame, e.g. after switching the view, are ended as well.
+    fn apply_internal_changes(
+        &mut self,
+        internal_changes: Vec<(WidgetName, RequestedStateChangeFromWidget)>,
+    ) {
+        for (widget_name, state_change) in internal_changes {
+            let Some(widget) = self.widgets.get_mut(&widget_name) else {
+                error!("Internal state change for unknown widget {}", widget_name);
+                continue;
+            };
+            widget.state_changed(&state_change.key, &state_change.value);
+
+            match widget.scene_preview() {
+                Some(mut preview) => {
+                    self.scene_changes.append(&mut preview);
+                    self.preview_widget = Some(widget_name);
+                }
+                None if self.preview_widget.as_ref() == Some(&widget_name) => {
+                    self.end_preview(widget_name);
+                }
+                None => (),
+            }
+        }
+
+        if let Some(widget_name) = self.preview_widget.clone() {
+            if !self.rendered_widgets.contains(&widget_name) {
+                if let Some(widget) = self.widgets.get_mut(&widget_name) {
+                    widget.end_preview();
+                }
+                self.end_preview(widget_name);
+            }
+        }
+    }
+
+    /// Restore the scene from the state of all widgets after the given widget ended its preview.
+    fn end_preview(&mut self, widget_name: WidgetName) {
+        self.preview_widget = None;
+        let mut widget_names = self.widgets.keys().cloned().collect::<Vec<_>>();
+        // Restore the scene state of the widget that ended the preview last
+        widget_names.retain(|name| name != &widget_name);
+        if self.widgets.contains_key(&widget_name) {
+            widget_names.push(widget_name);
+        }
+        for widget_name in widget_names {
+            match self.scene_modifications(&widget_name) {
+                Ok(mut changes) => self.scene_changes.append(&mut changes),
+                Err(e) => error!("Failed to restore scene: {:?}", e),
+            }
+        }
+    }
+
+    /// Scene modifications for the current state of the given widget.
+    fn scene_modifications(
+        &self,
+        widget_name: &str,
+    ) -> Result<Vec<SceneModificationEvent>, DeviceModelError> {
+        let widget = self
+            .widgets
+            .get(widget_name)
+            .ok_or(DeviceModelError::WidgetNotFound(widget_name.to_string()))?;
+        let widget_settings = self.widget_settings.get(widget_name).ok_or(
+            DeviceModelError::WidgetSettingsNotFound(widget_name.to_string()),
+        )?;
+
+        let mut scene_changes = vec![];
+        for (entity, modifications) in &widget_settings.scene_modifications.config {
+            for modification in modifications {
+                scene_changes.append(&mut widget.state_to_blender(entity, *modification));
             }
         }
+        Ok(scene_changes)
+    }
+
+    /// Return scene modifications not triggered by the backend.
+    pub(crate) fn take_scene_changes(&mut self) -> Vec<SceneModificationEvent> {
+        std::mem::take(&mut self.scene_changes)
+    }
+
+    /// Return the widget of the current view that modifies the given entity in the 3D scene.
+    pub(crate) fn widget_for_entity(&self, entity_name: &str) -> Option<&WidgetName> {
+        let current_view = self.current_view.as_ref()?;
+        self.views.get(current_view)?.iter().find(|widget_name| {
+            self.widget_settings
+                .get(*widget_name)
+                .map(|settings| {
+                    settings
+                        .scene_modifications
+                        .config
+                        .contains_key(entity_name)
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// Forward an interaction with the 3D scene to the given widget.
+    ///
+    /// Returns the state changes the widget requested in response.
+    pub(crate) fn scene_interaction(
+        &mut self,
+        widget_name: &str,
+        interaction: SceneInteraction,
+    ) -> Result<Vec<RequestedStateChange>, DeviceModelError> {
+        let widget = self
+            .widgets
+            .get(widget_name)
+            .ok_or(DeviceModelError::WidgetNotFound(widget_name.to_string()))?;
+        let widget_settings = self.widget_settings.get(widget_name).ok_or(
+            DeviceModelError::WidgetSettingsNotFound(widget_name.to_string()),
+        )?;
 
+        let widget_requests = match interaction {
+            SceneInteraction::Click => widget.scene_clicked(widget_name),
+            SceneInteraction::LongPress => {
+                vec![openhab::WidgetInteraction::FullscreenRequest(true)]
+            }
+        };
+
+        let mut internal_changes = vec![];
+        let requests = DeviceModel::handle_widget_interactions(
+            &mut self.fullscreen_widget,
+            &mut internal_changes,
+            widget_name,
+            &widget_settings.item_list,
+            widget_requests,
+        );
+
+        self.apply_internal_changes(internal_changes);
+        self.apply_optimistic_changes(&requests);
         Ok(requests)
     }
 
+    /// Show requested state changes in the widgets right away, until the backend confirms them.
+    ///
+    /// Only changes of keys the requesting widget shows optimistically are applied, see
+    /// [`Item::is_optimistic`].
+    fn apply_optimistic_changes(&mut self, requests: &[RequestedStateChange]) {
+        for request in requests {
+            let is_optimistic = self
+                .smart_home_items
+                .get(&request.item)
+                .map_or(false, |widgets| {
+                    widgets.iter().any(|(widget_name, key)| {
+                        widget_name == &request.widget
+                            && self
+                                .widgets
+                                .get(widget_name)
+                                .map_or(false, |widget| widget.is_optimistic(key))
+                    })
+                });
+            if !is_optimistic {
+                continue;
+            }
+            self.pending
+                .requested(&request.item, &request.value, history::now());
+            self.set_change_status(&request.item, &request.value, ChangeStatus::Pending);
+        }
+    }
+
+    /// Pass the given state of an item to its widgets, together with the status of the change.
+    fn set_change_status(&mut self, item_name: &str, state: &str, status: ChangeStatus) {
+        let Some(widgets) = self.smart_home_items.get(item_name).cloned() else {
+            return;
+        };
+        for (widget_name, key) in widgets {
+            let Some(widget) = self.widgets.get_mut(&widget_name) else {
+                continue;
+            };
+            widget.state_changed(&key, state);
+            widget.change_status(&key, status);
+            match self.scene_modifications(&widget_name) {
+                Ok(mut changes) => self.scene_changes.append(&mut changes),
+                Err(e) => error!("Failed to update scene of {}: {:?}", widget_name, e),
+            }
+        }
+    }
+
+    /// Sending a state change to the backend failed.
+    ///
+    /// The widgets are reset to the state the backend reported last, or to no state if it didn't
+    /// report any yet.
+    pub(crate) fn command_failed(&mut self, item_name: &str, reason: &str) {
+        error!("Changing the state of {} failed: {}", item_name, reason);
+        if let Some(state) = self.pending.failed(item_name) {
+            let state = state.as_deref().unwrap_or(NULL_STATE);
+            self.set_change_status(item_name, state, ChangeStatus::Failed);
+        }
+    }
+
+    /// Sending a state change to the backend failed, it is retried at the given time.
+    ///
+    /// The widgets keep showing the requested state until the retry is confirmed or times out.
+    pub(crate) fn command_retrying(&mut self, item_name: &str, retry_at: f64) {
+        self.pending.resent(item_name, retry_at);
+    }
+
+    /// Reset widgets whose state changes were not confirmed by the backend in time.
+    fn expire_pending_changes(&mut self) {
+        for (item_name, state) in self.pending.take_expired(history::now()) {
+            error!(
+                "State change of {} was not confirmed by the backend",
+                item_name
+            );
+            let state = state.as_deref().unwrap_or(NULL_STATE);
+            self.set_change_status(&item_name, state, ChangeStatus::Failed);
+        }
+    }
+
     pub(crate) fn state_changed(
         &mut self,
         item_name: &str,
@@ -270,6 +807,15 @@ impl DeviceModel {
             .get(item_name)
             .ok_or(DeviceModelError::ItemNotFound(item_name.to_string()))?;
 
+        self.history.record(item_name, state);
+
+        // Keep showing a requested state until the backend confirms it
+        let was_pending = self.pending.is_pending(item_name);
+        if !self.pending.state_received(item_name, state) {
+            bevy::log::debug!("Change of {} pending, ignoring state {}", item_name, state);
+            return Ok(scene_changes);
+        }
+
         for (widget_name, key) in widgets {
             let widget = self
                 .widgets
@@ -278,18 +824,14 @@ impl DeviceModel {
 
             // Execute state change in widget
             widget.state_changed(key, &state);
+            if was_pending {
+                widget.change_status(key, ChangeStatus::Confirmed);
+            }
 
             // Generate a list of scene modifications triggered from this widget
             // XXX We could obivously optimize this more, if it's needed, and "send" only state mofications
             // that can be triggered by that state change.
-            let widget_settings = self.widget_settings.get(widget_name).ok_or(
-                DeviceModelError::WidgetSettingsNotFound(widget_name.to_string()),
-            )?;
-            for (entity, modifications) in &widget_settings.scene_modifications.config {
-                for modification in modifications {
-                    scene_changes.append(&mut widget.state_to_blender(entity, *modification));
-                }
-            }
+            scene_changes.append(&mut self.scene_modifications(widget_name)?);
         }
         Ok(scene_changes)
     }
@@ -305,7 +847,411 @@ impl DeviceModel {
         self.state_changed(item_name, &state)
     }
 
+    /// The backend's persistence service returned the history of an item.
+    pub(crate) fn history_received(&mut self, item_name: &str, datapoints: Vec<[f64; 2]>) {
+        for (widget_name, key) in self.smart_home_items.get(item_name).into_iter().flatten() {
+            if let Some(widget) = self.widgets.get_mut(widget_name) {
+                if widget.history_keys().contains(&key.as_str()) {
+                    widget.history_received(key, &datapoints);
+                }
+            }
+        }
+        self.history.insert(item_name, datapoints);
+    }
+
+    /// Return history requests to be sent to the backend's persistence service.
+    pub(crate) fn take_history_requests(&mut self) -> Vec<(ItemName, HistoryRange)> {
+        self.history.take_requests()
+    }
+
+    /// Collect notifications of all widgets in all views.
+    pub(crate) fn notifications(&self) -> Vec<Notification> {
+        let mut notifications = vec![];
+        for (view_name, widget_names) in &self.views {
+            for widget_name in widget_names {
+                let Some(status) = self
+                    .widgets
+                    .get(widget_name)
+                    .and_then(|widget| widget.get_notification_status())
+                else {
+                    continue;
+                };
+                notifications.push(Notification {
+                    view_name: view_name.to_string(),
+                    widget_name: widget_name.to_string(),
+                    label: self.widget_label(widget_name),
+                    room: self
+                        .widget_settings
+                        .get(widget_name)
+                        .and_then(|s| s.room.clone()),
+                    status,
+                });
+            }
+        }
+        notifications
+    }
+
+    /// Label of the given widget, or its name if it doesn't have one.
+    pub(crate) fn widget_label(&self, widget_name: &str) -> String {
+        self.widget_settings
+            .get(widget_name)
+            .and_then(|s| s.label.clone())
+            .unwrap_or(widget_name.to_string())
+    }
+
+    /// Sliders of all widgets that currently want to show one, most important first.
+    ///
+    /// Each entry is the widget's name and label, the slider template and the widget's state.
+    pub(crate) fn active_sliders(
+        &self,
+    ) -> Vec<(
+        WidgetName,
+        String,
+        String,
+        serde_json::Map<String, serde_json::Value>,
+    )> {
+        let mut sliders = vec![];
+        for (widget_name, widget) in &self.widgets {
+            if let Some((template, priority)) = widget.render_slider() {
+                sliders.push((
+                    priority,
+                    widget_name.to_string(),
+                    self.widget_label(widget_name),
+                    template,
+                    widget.state_as_json(),
+                ));
+            }
+        }
+        sliders.sort_by(|a, b| b.0.cmp(&a.0).then(a.2.cmp(&b.2)));
+        sliders
+            .into_iter()
+            .map(|(_, widget_name, label, template, state)| (widget_name, label, template, state))
+            .collect()
+    }
+
+    /// Position of the given widget in the 3D scene, if it is rendered.
+    pub(crate) fn widget_position(&self, widget_name: &str) -> Option<Vec3> {
+        self.widget_settings
+            .get(widget_name)?
+            .render_settings
+            .as_ref()
+            .map(|s| s.world_position())
+    }
+
+    /// Move the given widget to a position in the 3D scene, updating its configuration.
+    pub(crate) fn move_widget(&mut self, widget_name: &str, position: Vec3) {
+        let Some(render_settings) = self
+            .widget_settings
+            .get_mut(widget_name)
+            .and_then(|settings| settings.render_settings.as_mut())
+        else {
+            return;
+        };
+        render_settings.left = position.x;
+        render_settings.top = position.z;
+        self.set_configured_position(widget_name, position);
+    }
+
+    /// Place a widget that isn't shown in the 3D scene yet at the given position, updating its
+    /// configuration.
+    pub(crate) fn place_widget(&mut self, widget_name: &str, position: Vec3) {
+        if self.widget_position(widget_name).is_some() {
+            self.move_widget(widget_name, position);
+            return;
+        }
+        let Some(view_name) = self
+            .views
+            .iter()
+            .find(|(_, widget_names)| widget_names.iter().any(|name| name == widget_name))
+            .map(|(view_name, _)| view_name.clone())
+        else {
+            return;
+        };
+        self.set_configured_position(widget_name, position);
+
+        let Some(widget_config) = self
+            .configuration
+            .as_ref()
+            .and_then(|configuration| configuration.widget(widget_name))
+        else {
+            return;
+        };
+        match WidgetRenderSetting::from_widget_config(&view_name, widget_name, widget_config) {
+            Ok(render_settings) => {
+                if let Some(widget_settings) = self.widget_settings.get_mut(widget_name) {
+                    widget_settings.render_settings = render_settings;
+                }
+            }
+            Err(errors) => error!("Failed to place widget {}: {:?}", widget_name, errors),
+        }
+    }
+
+    /// Keep the position in the configuration in sync, so the widget isn't rebuilt when
+    /// reloading.
+    fn set_configured_position(&mut self, widget_name: &str, position: Vec3) {
+        if let Some(widget_config) = self
+            .configuration
+            .as_mut()
+            .and_then(|configuration| configuration.widget_mut(widget_name))
+        {
+            widget_config.left = Some(format!("{:.2}%", position.x));
+            widget_config.top = Some(format!("{:.2}%", -position.z));
+        }
+    }
+
+    /// Restore the local history and the state kept by widgets from a previous run.
+    ///
+    /// Widgets learning from past values get the local history and the backend's persistence
+    /// service is asked for more.
+    pub(crate) fn load_state(&mut self) {
+        self.history.load();
+
+        if let Some(data) = history::read_storage(WIDGET_STATE_KEY) {
+            match serde_json::from_str::<HashMap<WidgetName, serde_json::Value>>(&data) {
+                Ok(states) => {
+                    for (widget_name, state) in &states {
+                        if let Some(widget) = self.widgets.get_mut(widget_name) {
+                            widget.restore_state(state);
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to parse stored widget state: {:?}", e),
+            }
+        }
+
+        for (widget_name, widget) in self.widgets.iter_mut() {
+            let Some(widget_settings) = self.widget_settings.get(widget_name) else {
+                continue;
+            };
+            for (key, item_name) in &widget_settings.item_list.items {
+                if widget.history_keys().contains(&key.as_str()) {
+                    widget.history_received(key, &self.history.raw_points(item_name));
+                    self.history.request_range(item_name, LEARNING_RANGE);
+                }
+            }
+        }
+    }
+
+    /// Store the local history and the state kept by widgets, so they are available after
+    /// restarting.
+    pub(crate) fn save_state(&self) {
+        self.history.save();
+
+        let states = self
+            .widgets
+            .iter()
+            .filter_map(|(widget_name, widget)| Some((widget_name, widget.stored_state()?)))
+            .collect::<HashMap<_, _>>();
+        let data = serde_json::to_string(&states).unwrap_or_default();
+        if let Err(e) = history::write_storage(WIDGET_STATE_KEY, &data) {
+            error!("Failed to store widget state: {}", e);
+        }
+    }
+
+    /// Configuration the widgets have been built from.
+    pub(crate) fn configuration(&self) -> Option<&ItemConfiguration> {
+        self.configuration.as_ref()
+    }
+
     pub fn get_items(&self) -> Vec<&String> {
         self.smart_home_items.keys().collect::<Vec<&String>>()
     }
 }
+
+#[test]
+fn test_config_errors() {
+    let configuration: ItemConfiguration = serde_json::from_str(
+        r#"{"zooms": {}, "views": {
+            "floorplan": {"order": 0, "blenderHide": [], "items": {
+                "Kitchen": {"itemType": "Switch", "blenderItems": {}, "smarthomeItems": {},
+                    "top": "10%", "left": "20%"},
+                "Hallway": {"itemType": "Swtich", "blenderItems": {"Lamp": ["Glow"]},
+                    "smarthomeItems": {}},
+                "Bath": {"itemType": "Climate", "blenderItems": {}, "smarthomeItems": {},
+                    "templateConf": {"setpointStep": "half"}}}},
+            "mobile": {"order": 1, "blenderHide": [], "items": {
+                "Kitchen": {"itemType": "Switch", "blenderItems": {}, "smarthomeItems": {}}}}}}"#,
+    )
+    .unwrap();
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&configuration);
+
+    // Valid widgets are still loaded, views can share them
+    assert_eq!(
+        device_model.widgets.keys().collect::<Vec<_>>(),
+        vec!["Kitchen"]
+    );
+    assert_eq!(device_model.views["floorplan"], vec!["Kitchen"]);
+    assert_eq!(device_model.views["mobile"], vec!["Kitchen"]);
+    let errors = device_model
+        .config_errors()
+        .iter()
+        .map(|e| e.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "views.floorplan.items.Bath.templateConf",
+            "views.floorplan.items.Hallway.blenderItems.Lamp[0]",
+            "views.floorplan.items.Hallway.itemType",
+        ]
+    );
+}
+
+#[test]
+fn test_missing_key_is_skipped() {
+    let item_list = WidgetItemList {
+        items: vec![("volume".to_string(), "Kitchen_Volume".to_string())],
+    };
+    let mut fullscreen_widget = None;
+    let mut internal_changes = vec![];
+    let requests = DeviceModel::handle_widget_interactions(
+        &mut fullscreen_widget,
+        &mut internal_changes,
+        "Kitchen",
+        &item_list,
+        vec![
+            openhab::WidgetInteraction::state_change("controller", "PLAY"),
+            openhab::WidgetInteraction::state_change("volume", "20"),
+        ],
+    );
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].item, "Kitchen_Volume");
+    assert_eq!(requests[0].value, "20");
+}
+
+#[test]
+fn test_preview_ends_when_not_rendered() {
+    let configuration: ItemConfiguration = serde_json::from_str(
+        r#"{"zooms": {}, "views": {
+            "floorplan": {"order": 0, "blenderHide": [], "items": {
+                "Scenes": {"itemType": "Scene", "blenderItems": {}, "smarthomeItems": {},
+                    "top": "10%", "left": "20%",
+                    "templateConf": {"scenes": [["1", "Cozy", "🛋"]],
+                        "lights": {"1": {"Lamp": 30.0}}}}}}}}"#,
+    )
+    .unwrap();
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&configuration);
+
+    let preview = RequestedStateChangeFromWidget {
+        key: format!("{}preview", INTERNAL_STATE_PREFIX),
+        value: "1".to_string(),
+    };
+    device_model.rendered_widgets.insert("Scenes".to_string());
+    device_model.apply_internal_changes(vec![("Scenes".to_string(), preview)]);
+    assert_eq!(device_model.preview_widget.as_deref(), Some("Scenes"));
+
+    // The widget is no longer rendered, e.g. after switching the view
+    device_model.rendered_widgets.clear();
+    device_model.apply_internal_changes(vec![]);
+    assert_eq!(device_model.preview_widget, None);
+    assert!(device_model.widgets["Scenes"].scene_preview().is_none());
+}
+
+#[test]
+fn test_optimistic_change_reverted() {
+    let configuration: ItemConfiguration = serde_json::from_str(
+        r#"{"zooms": {}, "views": {
+            "floorplan": {"order": 0, "blenderHide": [], "items": {
+                "Kitchen": {"itemType": "Switch", "blenderItems": {},
+                    "smarthomeItems": {"Kitchen_Light": {"key": "Kitchen"}}}}}}}"#,
+    )
+    .unwrap();
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&configuration);
+
+    let requests = device_model
+        .scene_interaction("Kitchen", SceneInteraction::Click)
+        .unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].value, "ON");
+    assert!(device_model.pending.is_pending("Kitchen_Light"));
+    assert!(device_model.widgets["Kitchen"]
+        .get_notification_status()
+        .is_some());
+
+    // The item never had a state, the switch is off again
+    device_model.command_failed("Kitchen_Light", "timeout");
+    assert!(!device_model.pending.is_pending("Kitchen_Light"));
+    assert!(device_model.widgets["Kitchen"]
+        .get_notification_status()
+        .is_none());
+}
+
+#[test]
+fn test_reload_keeps_unchanged_widgets() {
+    let config = |hallway_label: &str| -> ItemConfiguration {
+        serde_json::from_str(&format!(
+            r#"{{"zooms": {{}}, "views": {{
+                "floorplan": {{"order": 0, "blenderHide": [], "items": {{
+                    "Kitchen": {{"itemType": "Switch", "blenderItems": {{}},
+                        "smarthomeItems": {{"Kitchen_Light": {{"key": "Kitchen"}}}}}},
+                    "Hallway": {{"itemType": "Switch", "label": "{}",
+                        "blenderItems": {{"Hallway_Lamp": ["Energy"]}},
+                        "smarthomeItems": {{"Hallway_Light": {{"key": "Hallway"}}}}}}}}}}}}}}"#,
+            hallway_label
+        ))
+        .unwrap()
+    };
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&config("Hallway"));
+
+    // Switched on, but not yet confirmed by the backend
+    for widget_name in ["Kitchen", "Hallway"] {
+        device_model
+            .scene_interaction(widget_name, SceneInteraction::Click)
+            .unwrap();
+    }
+    device_model.take_scene_changes();
+
+    let unknown_items = device_model.reload(&config("Corridor"));
+    assert_eq!(unknown_items, vec!["Hallway_Light"]);
+    assert!(device_model.widgets["Kitchen"]
+        .get_notification_status()
+        .is_some());
+    assert!(device_model.widgets["Hallway"]
+        .get_notification_status()
+        .is_none());
+    assert_eq!(device_model.widget_label("Hallway"), "Corridor");
+
+    // The lamp of the rebuilt widget is reset
+    let scene_changes = device_model.take_scene_changes();
+    assert!(matches!(
+        scene_changes.first(),
+        Some(SceneModificationEvent::LightModification(crate::events::LightModification {
+            entity_name,
+            illuminance_percentage,
+        })) if entity_name == "Hallway_Lamp" && *illuminance_percentage == 0.
+    ));
+}
+
+#[test]
+fn test_place_widget() {
+    let configuration: ItemConfiguration = serde_json::from_str(
+        r#"{"zooms": {}, "views": {
+            "floorplan": {"order": 0, "blenderHide": [], "items": {
+                "Kitchen": {"itemType": "Switch", "blenderItems": {}}}}}}"#,
+    )
+    .unwrap();
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&configuration);
+    assert_eq!(device_model.widget_position("Kitchen"), None);
+
+    device_model.place_widget("Kitchen", Vec3::new(2., 0.5, -3.));
+    assert_eq!(
+        device_model.widget_position("Kitchen"),
+        Some(Vec3::new(2., WIDGET_HEIGHT, -3.))
+    );
+    let widget_config = device_model
+        .configuration()
+        .and_then(|configuration| configuration.widget("Kitchen"))
+        .unwrap();
+    assert_eq!(widget_config.left.as_deref(), Some("2.00%"));
+    assert_eq!(widget_config.top.as_deref(), Some("3.00%"));
+
+    // The placed widget is kept when reloading its configuration
+    let configuration = device_model.configuration().cloned().unwrap();
+    device_model.reload(&configuration);
+    assert!(device_model.widget_position("Kitchen").is_some());
+}
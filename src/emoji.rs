@@ -2,18 +2,23 @@ use bevy_egui::egui::Image;
 
 pub(crate) const LIGHT_BULB: &str = "1F4A1";
 
-pub(crate) fn get_emoji(code: &str) -> Image {
+/// Image of the emoji with the given code, `None` if it isn't bundled.
+pub(crate) fn get_emoji(code: &str) -> Option<Image<'static>> {
     match code {
-        // Light bulb
-        "1F4A1" => Image::from_bytes(
-            "bytes://1F4A1.png",
-            include_bytes!("../assets/emoji/1F4A1.png"),
-        ),
+        LIGHT_BULB => Some(light_bulb()),
         //  Coffee machine
-        "E150" => Image::from_bytes(
+        "E150" => Some(Image::from_bytes(
             "bytes://E150.png",
             include_bytes!("../assets/emoji/E150.png"),
-        ),
-        _ => panic!("Could not find image {}", code),
+        )),
+        _ => None,
     }
 }
+
+/// Image of the light bulb, the default icon of lights and switches.
+pub(crate) fn light_bulb() -> Image<'static> {
+    Image::from_bytes(
+        "bytes://1F4A1.png",
+        include_bytes!("../assets/emoji/1F4A1.png"),
+    )
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+
+use crate::events::EnergyFlowModification;
+use crate::utils::scale_value;
+
+/// Power represented by a single particle on a path.
+const WATTS_PER_PARTICLE: f32 = 250.;
+
+/// Upper limit of particles per path, so large consumers don't flood the scene.
+const MAX_PARTICLES: usize = 20;
+
+/// Speed of the particles in meters per second depending on the power in Watts.
+const SPEED_MAPPING: [(f32, f32); 2] = [(0., 0.3), (5000., 2.)];
+
+/// Hue of the particles depending on the power in Watts: yellow for low and red for high power.
+const HUE_MAPPING: [(f32, f32); 2] = [(0., 60.), (5000., 0.)];
+
+/// Hue of particles for negative power, e.g. PV export or charging a battery.
+const HUE_NEGATIVE: f32 = 120.;
+
+const PARTICLE_SIZE: f32 = 0.05;
+
+/// Marks a particle moving along an energy flow path.
+#[derive(Component)]
+pub(crate) struct EnergyFlow {
+    path_name: String,
+    /// Position of the particle on the path at time zero, relative to the length of the path.
+    offset: f32,
+}
+
+struct FlowState {
+    /// Points of the path, `None` until resolved from the mesh of the same name.
+    points: Option<Vec<Vec3>>,
+    power: f32,
+    material: Option<Handle<StandardMaterial>>,
+    /// Set when the power changed and the particles need to be updated.
+    dirty: bool,
+}
+
+/// Energy flows shown in the scene, by name of their path.
+#[derive(Default, Resource)]
+pub(crate) struct EnergyFlows {
+    flows: HashMap<String, FlowState>,
+    particle_mesh: Option<Handle<Mesh>>,
+}
+
+/// Length of the given polyline.
+pub(crate) fn polyline_length(points: &[Vec3]) -> f32 {
+    points.windows(2).map(|w| w[0].distance(w[1])).sum()
+}
+
+/// Point at the relative position `t` in `[0, 1]` along the given polyline.
+pub(crate) fn point_on_polyline(points: &[Vec3], t: f32) -> Vec3 {
+    let mut remaining = t.clamp(0., 1.) * polyline_length(points);
+    for w in points.windows(2) {
+        let segment_length = w[0].distance(w[1]);
+        if remaining <= segment_length && segment_length > 0. {
+            return w[0].lerp(w[1], remaining / segment_length);
+        }
+        remaining -= segment_length;
+    }
+    points.last().copied().unwrap_or_default()
+}
+
+#[test]
+fn test_point_on_polyline() {
+    let points = vec![
+        Vec3::new(0., 0., 0.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(2., 0., 2.),
+    ];
+    assert_eq!(polyline_length(&points), 4.);
+    assert_eq!(point_on_polyline(&points, 0.), Vec3::new(0., 0., 0.));
+    assert_eq!(point_on_polyline(&points, 0.25), Vec3::new(1., 0., 0.));
+    assert_eq!(point_on_polyline(&points, 0.75), Vec3::new(2., 0., 1.));
+    assert_eq!(point_on_polyline(&points, 1.), Vec3::new(2., 0., 2.));
+    assert_eq!(point_on_polyline(&[], 0.5), Vec3::ZERO);
+}
+
+fn particle_color(power: f32) -> Color {
+    let hue = if power < 0. {
+        HUE_NEGATIVE
+    } else {
+        scale_value(power, &HUE_MAPPING)
+    };
+    Color::hsl(hue, 1., 0.5)
+}
+
+/// Remember the latest power of each energy flow.
+pub(crate) fn update_energy_flows(
+    mut ev_energy_flow: EventReader<EnergyFlowModification>,
+    mut energy_flows: ResMut<EnergyFlows>,
+) {
+    for ev in ev_energy_flow.read() {
+        let flow = energy_flows
+            .flows
+            .entry(ev.path_name.to_string())
+            .or_insert(FlowState {
+                points: None,
+                power: 0.,
+                material: None,
+                dirty: true,
+            });
+        if ev.points.is_some() {
+            flow.points = ev.points.clone();
+        }
+        flow.dirty |= flow.power != ev.power;
+        flow.power = ev.power;
+    }
+}
+
+/// Resolve paths which are not given in the configuration from the meshes of the glTF scene.
+///
+/// Curves in Blender have to be converted to meshes before exporting. Their vertices, in order,
+/// are used as the points of the path.
+pub(crate) fn resolve_energy_flow_paths(
+    mut energy_flows: ResMut<EnergyFlows>,
+    scene_meshes: Query<(
+        &Handle<Mesh>,
+        &GlobalTransform,
+        Option<&Name>,
+        Option<&Parent>,
+    )>,
+    names: Query<&Name>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (path_name, flow) in energy_flows
+        .flows
+        .iter_mut()
+        .filter(|(_, flow)| flow.points.is_none())
+    {
+        // glTF nodes carry the name, the mesh is usually a child of it
+        let found = scene_meshes.iter().find(|(_, _, name, parent)| {
+            name.map_or(false, |name| name.as_str() == path_name)
+                || parent
+                    .and_then(|parent| names.get(parent.get()).ok())
+                    .map_or(false, |name| name.as_str() == path_name)
+        });
+        let Some((mesh, transform, _, _)) = found else {
+            continue;
+        };
+        if let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+            .get(mesh)
+            .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+        {
+            info!("Resolved energy flow path {} from scene", path_name);
+            flow.points = Some(
+                positions
+                    .iter()
+                    .map(|p| transform.transform_point(Vec3::from(*p)))
+                    .collect(),
+            );
+            flow.dirty = true;
+        }
+    }
+}
+
+/// Spawn and despawn particles so their number on each path matches the flowing power, and
+/// color them by power.
+pub(crate) fn spawn_energy_flow_particles(
+    mut commands: Commands,
+    mut energy_flows: ResMut<EnergyFlows>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    particles: Query<(Entity, &EnergyFlow)>,
+) {
+    let energy_flows = &mut *energy_flows;
+    let particle_mesh = energy_flows
+        .particle_mesh
+        .get_or_insert_with(|| meshes.add(Sphere::new(PARTICLE_SIZE).mesh().ico(3).unwrap()))
+        .clone();
+
+    for (path_name, flow) in energy_flows
+        .flows
+        .iter_mut()
+        .filter(|(_, flow)| flow.dirty && flow.points.is_some())
+    {
+        flow.dirty = false;
+
+        let color = particle_color(flow.power);
+        let material = flow
+            .material
+            .get_or_insert_with(|| materials.add(StandardMaterial::default()))
+            .clone();
+        if let Some(material) = materials.get_mut(&material) {
+            material.base_color = color;
+            material.emissive = color;
+            material.reflectance = 0.;
+        }
+
+        let num_particles =
+            ((flow.power.abs() / WATTS_PER_PARTICLE).ceil() as usize).min(MAX_PARTICLES);
+        // Respawn all particles so they are evenly distributed along the path
+        for (entity, _) in particles.iter().filter(|(_, p)| &p.path_name == path_name) {
+            commands.entity(entity).despawn();
+        }
+        for i in 0..num_particles {
+            commands.spawn((
+                PbrBundle {
+                    mesh: particle_mesh.clone(),
+                    material: material.clone(),
+                    ..default()
+                },
+                EnergyFlow {
+                    path_name: path_name.to_string(),
+                    offset: i as f32 / num_particles as f32,
+                },
+            ));
+        }
+    }
+}
+
+/// Move the particles along their path. The more power, the faster they move.
+pub(crate) fn animate_paths(
+    mut particles: Query<(&EnergyFlow, &mut Transform)>,
+    energy_flows: Res<EnergyFlows>,
+    time: Res<Time>,
+) {
+    for (particle, mut transform) in &mut particles {
+        let Some(flow) = energy_flows.flows.get(&particle.path_name) else {
+            continue;
+        };
+        let Some(points) = &flow.points else {
+            continue;
+        };
+        let length = polyline_length(points);
+        if length <= 0. {
+            continue;
+        }
+
+        let speed = scale_value(flow.power.abs(), &SPEED_MAPPING);
+        let t = (particle.offset + time.elapsed_seconds() * speed / length).fract();
+        // Negative power flows in the opposite direction
+        let t = if flow.power < 0. { 1. - t } else { t };
+        transform.translation = point_on_polyline(points, t);
+    }
+}
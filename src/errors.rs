@@ -1,4 +1,5 @@
 use crate::openhab::RequestedStateChangeFromWidget;
+use thiserror::Error;
 
 #[derive(Debug, PartialEq)]
 pub enum DeviceModelError {
@@ -10,3 +11,45 @@ pub enum DeviceModelError {
     KeyNotFound(RequestedStateChangeFromWidget),
     NoViewSelected(()),
 }
+
+/// Problem found in the configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// JSON path of the offending value, e.g. `views.floorplan.items.Kitchen.top`.
+    pub path: String,
+    pub kind: ConfigErrorKind,
+}
+
+impl ConfigError {
+    /// Error in the configuration of the given widget, optionally for one of its fields.
+    pub(crate) fn widget(
+        view_name: &str,
+        widget_name: &str,
+        field: Option<&str>,
+        kind: ConfigErrorKind,
+    ) -> Self {
+        let mut path = format!("views.{}.items.{}", view_name, widget_name);
+        if let Some(field) = field {
+            path = format!("{}.{}", path, field);
+        }
+        ConfigError { path, kind }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConfigErrorKind {
+    #[error("invalid coordinate {0:?}, expected a percentage like \"42%\"")]
+    InvalidCoordinate(String),
+    #[error("unknown scene modification {0:?}")]
+    UnknownSceneModification(String),
+    #[error("unknown item type {0:?}")]
+    UnknownItemType(String),
+    #[error("invalid template configuration: {0}")]
+    InvalidTemplateConf(String),
+}
@@ -5,6 +5,10 @@ use serde::Serialize;
 pub(crate) enum SceneModificationEvent {
     LightModification(LightModification),
     SunModification(SunModification),
+    EnergyFlowModification(EnergyFlowModification),
+    TintModification(TintModification),
+    PositionModification(PositionModification),
+    VisibilityModification(VisibilityModification),
 }
 
 #[derive(Event, Debug)]
@@ -13,6 +17,40 @@ pub(crate) struct LightModification {
     pub(crate) illuminance_percentage: f32,
 }
 
+/// Power flowing along a path in the scene.
+#[derive(Event, Debug, Clone)]
+pub(crate) struct EnergyFlowModification {
+    /// Name of the path, either a polyline given in the configuration or a mesh in the scene.
+    pub(crate) path_name: String,
+    /// Points of the path, if given in the configuration.
+    pub(crate) points: Option<Vec<Vec3>>,
+    /// Power in Watts. Negative values reverse the direction of the flow.
+    pub(crate) power: f32,
+}
+
+/// Tint the meshes of an entity, e.g. to highlight a room.
+#[derive(Event, Debug, Clone)]
+pub(crate) struct TintModification {
+    pub(crate) entity_name: String,
+    /// Emissive color of the tint, `None` restores the original material.
+    pub(crate) color: Option<Color>,
+}
+
+/// Move an entity to a new position in the scene.
+#[derive(Event, Debug, Clone)]
+pub(crate) struct PositionModification {
+    pub(crate) entity_name: String,
+    /// New position of the entity, `None` restores its original position.
+    pub(crate) position: Option<Vec3>,
+}
+
+/// Show or hide an entity in the scene.
+#[derive(Event, Debug, Clone)]
+pub(crate) struct VisibilityModification {
+    pub(crate) entity_name: String,
+    pub(crate) visible: bool,
+}
+
 #[derive(Event, Debug, Serialize, PartialEq)]
 pub struct SunModification {
     pub(crate) illuminance: f64,
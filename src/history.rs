@@ -0,0 +1,561 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::log::{error, info, warn};
+use bevy_egui::egui;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use egui_plot::{Line, Plot};
+use serde::{Deserialize, Serialize};
+
+use crate::plot::PlotType;
+use crate::widget_settings::{ItemName, WidgetKey};
+
+/// Raw values are kept for this long, then they are downsampled into buckets.
+const RAW_RETENTION: f64 = 24. * 60. * 60.;
+/// Maximum number of raw values per item. If exceeded, values are downsampled earlier.
+const MAX_RAW_VALUES: usize = 500;
+/// Length of the buckets older values are downsampled to.
+const BUCKET_SECONDS: f64 = 60. * 60.;
+/// Buckets are dropped once they are older than this.
+const BUCKET_RETENTION: f64 = 30. * 24. * 60. * 60.;
+
+/// Name of the file (native) or local storage key (wasm) the history is stored in.
+const STORAGE_KEY: &str = "history.json";
+/// Upper bound for the size of the stored history. Browsers usually allow 5 MB of local storage.
+const MAX_STORAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Time ranges that can be selected for history charts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) enum HistoryRange {
+    #[default]
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryRange {
+    pub(crate) const ALL: [HistoryRange; 4] = [
+        HistoryRange::Hour,
+        HistoryRange::Day,
+        HistoryRange::Week,
+        HistoryRange::Month,
+    ];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            HistoryRange::Hour => "1h",
+            HistoryRange::Day => "24h",
+            HistoryRange::Week => "7d",
+            HistoryRange::Month => "30d",
+        }
+    }
+
+    /// Length of the range in seconds.
+    pub(crate) fn seconds(&self) -> f64 {
+        match self {
+            HistoryRange::Hour => 60. * 60.,
+            HistoryRange::Day => 24. * 60. * 60.,
+            HistoryRange::Week => 7. * 24. * 60. * 60.,
+            HistoryRange::Month => 30. * 24. * 60. * 60.,
+        }
+    }
+
+    /// Start of the range, relative to now, formatted the way the backend expects it.
+    pub(crate) fn start_time(&self) -> String {
+        (Utc::now() - chrono::Duration::seconds(self.seconds() as i64))
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+}
+
+/// Current time as seconds since the epoch, which is what we use as x-axis for all charts.
+pub(crate) fn now() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.
+}
+
+/// Convert a state of the backend to a value that can be plotted.
+///
+/// Numbers may carry a unit (e.g. "21.5 °C"), switches and contacts are mapped to 0 and 1.
+pub(crate) fn parse_value(state: &str) -> Option<f64> {
+    match state {
+        "ON" | "OPEN" => Some(1.),
+        "OFF" | "CLOSED" => Some(0.),
+        _ => state.split(' ').next()?.parse::<f64>().ok(),
+    }
+}
+
+/// Turn a series of points into the points of a step function.
+///
+/// Each value is held until the next value is reached.
+fn to_steps(points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut steps = vec![];
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 {
+            steps.push([point[0], points[i - 1][1]]);
+        }
+        steps.push(*point);
+    }
+    steps
+}
+
+/// Minimum, maximum and average of all values of an item within `BUCKET_SECONDS`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Bucket {
+    /// Start of the bucket in seconds since the epoch.
+    pub(crate) start: f64,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) avg: f64,
+    /// Number of values in this bucket.
+    pub(crate) count: u32,
+}
+
+impl Bucket {
+    fn new(start: f64, value: f64) -> Self {
+        Bucket {
+            start,
+            min: value,
+            max: value,
+            avg: value,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.avg = (self.avg * self.count as f64 + value) / (self.count + 1) as f64;
+        self.count += 1;
+    }
+
+    /// Add the values of another bucket with the same start.
+    fn merge(&mut self, other: &Bucket) {
+        let count = self.count + other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.avg = (self.avg * self.count as f64 + other.avg * other.count as f64) / count as f64;
+        self.count = count;
+    }
+}
+
+/// All values we know of a single item.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct Series {
+    /// Recent values as pairs of timestamp (seconds since the epoch) and value. Sorted by time.
+    raw: Vec<[f64; 2]>,
+    /// Older values, downsampled. Sorted by time.
+    buckets: Vec<Bucket>,
+    /// Time of the most recent value that was downsampled, values up to then are already counted
+    /// in the buckets.
+    #[serde(default)]
+    bucketed_until: f64,
+}
+
+impl Series {
+    fn has_bucket(&self, time: f64) -> bool {
+        let start = (time / BUCKET_SECONDS).floor() * BUCKET_SECONDS;
+        self.buckets
+            .binary_search_by(|bucket| bucket.start.total_cmp(&start))
+            .is_ok()
+    }
+
+    fn add_to_bucket(&mut self, time: f64, value: f64) {
+        let start = (time / BUCKET_SECONDS).floor() * BUCKET_SECONDS;
+        match self
+            .buckets
+            .binary_search_by(|bucket| bucket.start.total_cmp(&start))
+        {
+            Ok(i) => self.buckets[i].add(value),
+            Err(i) => self.buckets.insert(i, Bucket::new(start, value)),
+        }
+    }
+
+    /// Downsample old raw values and drop buckets that are no longer needed.
+    fn compact(&mut self, now: f64) {
+        let num_old = self.raw.partition_point(|p| p[0] < now - RAW_RETENTION);
+        let num_old = num_old.max(self.raw.len().saturating_sub(MAX_RAW_VALUES));
+        for [time, value] in self.raw.drain(..num_old).collect::<Vec<[f64; 2]>>() {
+            self.add_to_bucket(time, value);
+            self.bucketed_until = self.bucketed_until.max(time);
+        }
+
+        self.buckets
+            .retain(|bucket| bucket.start + BUCKET_SECONDS > now - BUCKET_RETENTION);
+    }
+
+    /// Add values, skipping the ones that are already known.
+    ///
+    /// Values are identified by their timestamp. Downsampled values can't be told apart anymore,
+    /// so values up to the last downsampled one are skipped if their bucket exists.
+    fn merge_raw(&mut self, datapoints: Vec<[f64; 2]>) {
+        let datapoints = datapoints
+            .into_iter()
+            .filter(|[time, _]| *time > self.bucketed_until || !self.has_bucket(*time))
+            .collect::<Vec<_>>();
+        self.raw.extend(datapoints);
+        self.raw.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        self.raw.dedup_by(|a, b| a[0] == b[0]);
+    }
+
+    /// Add the values of a series stored by a previous run.
+    fn merge(&mut self, stored: Series) {
+        for bucket in &stored.buckets {
+            match self
+                .buckets
+                .binary_search_by(|b| b.start.total_cmp(&bucket.start))
+            {
+                Ok(i) => self.buckets[i].merge(bucket),
+                Err(i) => self.buckets.insert(i, *bucket),
+            }
+        }
+        self.bucketed_until = self.bucketed_until.max(stored.bucketed_until);
+        self.merge_raw(stored.raw);
+    }
+
+    /// All values, with buckets represented by their average. Sorted by time.
+    fn points(&self) -> Vec<[f64; 2]> {
+        let mut points = self
+            .buckets
+            .iter()
+            .map(|bucket| [bucket.start + BUCKET_SECONDS / 2., bucket.avg])
+            .chain(self.raw.iter().cloned())
+            .collect::<Vec<[f64; 2]>>();
+        points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        points
+    }
+}
+
+/// Read data stored under the given key, from a file (native) or local storage (wasm).
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_storage(key: &str) -> Option<String> {
+    std::fs::read_to_string(key).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_storage(key: &str, data: &str) -> Result<(), String> {
+    std::fs::write(key, data).map_err(|e| e.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn remove_storage(key: &str) -> Result<(), String> {
+    std::fs::remove_file(key).map_err(|e| e.to_string())
+}
+
+// IndexedDB would allow storing more, but its API is asynchronous only. We deliberately use the
+// synchronous local storage and keep the history small enough to fit, see `MAX_STORAGE_BYTES`.
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn read_storage(key: &str) -> Option<String> {
+    local_storage()?.get_item(key).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn write_storage(key: &str, data: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("Local storage is not available".to_string())?
+        .set_item(key, data)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn remove_storage(key: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("Local storage is not available".to_string())?
+        .remove_item(key)
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// Local store of the state changes of all items.
+///
+/// Recent values are kept as they are, older ones are downsampled, so charts work even if the
+/// backend has no persistence service configured. The store is bounded in size and can be saved
+/// to disk (native) or local storage (wasm).
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct History {
+    /// Values of each item.
+    series: HashMap<ItemName, Series>,
+    /// The range currently selected for charts.
+    #[serde(skip)]
+    range: HistoryRange,
+    /// Items for which the history has to be requested from the backend.
+    #[serde(skip)]
+    requests: Vec<(ItemName, HistoryRange)>,
+    /// Items and ranges we already requested, so we only do that once.
+    #[serde(skip)]
+    requested: HashSet<(ItemName, HistoryRange)>,
+}
+
+impl History {
+    /// Append a state change of an item received from the backend.
+    pub(crate) fn record(&mut self, item_name: &str, state: &str) {
+        if let Some(value) = parse_value(state) {
+            let now = now();
+            let series = self.series.entry(item_name.to_string()).or_default();
+            series.raw.push([now, value]);
+            series.compact(now);
+        }
+    }
+
+    /// Merge datapoints of an item received from the backend's persistence service.
+    pub(crate) fn insert(&mut self, item_name: &str, datapoints: Vec<[f64; 2]>) {
+        let series = self.series.entry(item_name.to_string()).or_default();
+        series.merge_raw(datapoints);
+        series.compact(now());
+    }
+
+    /// Restore the history stored by a previous run, if any.
+    ///
+    /// Values recorded before are kept.
+    pub(crate) fn load(&mut self) {
+        let Some(data) = read_storage(STORAGE_KEY) else {
+            return;
+        };
+        match serde_json::from_str::<History>(&data) {
+            Ok(history) => {
+                info!("Loaded history of {} items", history.series.len());
+                let now = now();
+                for (item_name, stored) in history.series {
+                    let series = self.series.entry(item_name).or_default();
+                    series.merge(stored);
+                    series.compact(now);
+                }
+            }
+            Err(e) => error!("Failed to parse stored history: {:?}", e),
+        }
+    }
+
+    /// Store the history, so it's available after restarting.
+    ///
+    /// If the history exceeds the size we allow, only the downsampled values are stored.
+    pub(crate) fn save(&self) {
+        let mut data = serde_json::to_string(self).unwrap_or_default();
+        if data.len() > MAX_STORAGE_BYTES {
+            warn!(
+                "History has {} bytes, only storing downsampled values",
+                data.len()
+            );
+            let series = self
+                .series
+                .iter()
+                .map(|(item_name, series)| {
+                    let buckets = Series {
+                        raw: vec![],
+                        buckets: series.buckets.clone(),
+                        bucketed_until: series.bucketed_until,
+                    };
+                    (item_name.to_string(), buckets)
+                })
+                .collect::<HashMap<ItemName, Series>>();
+            data = serde_json::to_string(&History {
+                series,
+                ..Default::default()
+            })
+            .unwrap_or_default();
+        }
+
+        if data.len() > MAX_STORAGE_BYTES {
+            error!("History has {} bytes, not storing it", data.len());
+        } else if let Err(e) = write_storage(STORAGE_KEY, &data) {
+            error!("Failed to store history: {}", e);
+        }
+    }
+
+    /// Remember to request the history of the given item for the currently selected range.
+    fn request(&mut self, item_name: &str) {
+        self.request_range(item_name, self.range);
+    }
+
+    /// Remember to request the history of the given item for the given range.
+    pub(crate) fn request_range(&mut self, item_name: &str, range: HistoryRange) {
+        let request = (item_name.to_string(), range);
+        if self.requested.insert(request.clone()) {
+            self.requests.push(request);
+        }
+    }
+
+    /// Return the history requests that have to be sent to the backend.
+    pub(crate) fn take_requests(&mut self) -> Vec<(ItemName, HistoryRange)> {
+        std::mem::take(&mut self.requests)
+    }
+
+    /// Recent values of the given item, which are not downsampled yet. Sorted by time.
+    pub(crate) fn raw_points(&self, item_name: &str) -> Vec<[f64; 2]> {
+        self.series
+            .get(item_name)
+            .map(|series| series.raw.clone())
+            .unwrap_or_default()
+    }
+
+    /// Datapoints of the given item within the currently selected range.
+    fn points(&self, item_name: &str, now: f64) -> Vec<[f64; 2]> {
+        let start = now - self.range.seconds();
+        let series = match self.series.get(item_name) {
+            Some(series) => series,
+            None => return vec![],
+        };
+
+        // Keep the last value before the range starts, so the chart begins with the value that
+        // was valid at that time.
+        let series = series.points();
+        let first = series.partition_point(|p| p[0] < start);
+        let mut points = series[first.saturating_sub(1)..].to_vec();
+        if let Some(last) = points.last().cloned() {
+            // The last value is still valid now.
+            points.push([now, last[1]]);
+        }
+        points
+    }
+
+    /// Render a window with a chart for each of the given keys.
+    ///
+    /// Each entry in `series` is the key of the widget, the backend item and how it should be
+    /// plotted.
+    pub(crate) fn render_window(
+        &mut self,
+        ctx: &mut egui::Context,
+        id: egui::Id,
+        title: &str,
+        series: &[(WidgetKey, ItemName, PlotType)],
+    ) {
+        for (_, item_name, _) in series {
+            self.request(item_name);
+        }
+
+        let now = now();
+        egui::Window::new(format!("{} history", title))
+            .id(id.with("history"))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for range in HistoryRange::ALL {
+                        if ui
+                            .selectable_label(self.range == range, range.label())
+                            .clicked()
+                        {
+                            self.range = range;
+                        }
+                    }
+                });
+
+                for (key, item_name, plot_type) in series {
+                    let points = self.points(item_name, now);
+                    let points = match plot_type {
+                        PlotType::LinePlot => points,
+                        PlotType::StepPlot => to_steps(&points),
+                    };
+
+                    ui.label(key);
+                    let range = self.range;
+                    Plot::new(id.with(("history", key)))
+                        .height(120.)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .include_x(now - range.seconds())
+                        .include_x(now)
+                        .x_axis_formatter(move |mark, _, _| {
+                            let format = match range {
+                                HistoryRange::Hour | HistoryRange::Day => "%H:%M",
+                                HistoryRange::Week | HistoryRange::Month => "%d.%m.",
+                            };
+                            Utc.timestamp_opt(mark.value as i64, 0)
+                                .single()
+                                .map(|t| t.format(format).to_string())
+                                .unwrap_or_default()
+                        })
+                        .show(ui, |plot_ui| plot_ui.line(Line::new(points)));
+                }
+            });
+
+        // A newly selected range might need more data.
+        for (_, item_name, _) in series {
+            self.request(item_name);
+        }
+    }
+}
+
+#[test]
+fn test_history_points() {
+    assert_eq!(parse_value("21.5 °C"), Some(21.5));
+    assert_eq!(parse_value("OPEN"), Some(1.));
+    assert_eq!(parse_value("NULL"), None);
+
+    let mut history = History::default();
+    let now = now();
+    history.insert("item", vec![[now - 7200., 1.], [now - 60., 2.]]);
+    history.insert("item", vec![[now - 120., 3.], [now - 60., 2.]]);
+    assert_eq!(
+        history.points("item", now),
+        vec![
+            [now - 7200., 1.],
+            [now - 120., 3.],
+            [now - 60., 2.],
+            [now, 2.]
+        ]
+    );
+
+    assert_eq!(
+        to_steps(&[[0., 1.], [1., 2.]]),
+        vec![[0., 1.], [1., 1.], [1., 2.]]
+    );
+}
+
+#[test]
+fn test_history_downsampling() {
+    let now = 100. * BUCKET_SECONDS;
+    let mut series = Series::default();
+    series.raw = vec![
+        [now - 2. * RAW_RETENTION, 5.],
+        [now - RAW_RETENTION - 2. * BUCKET_SECONDS, 1.],
+        [now - RAW_RETENTION - 2. * BUCKET_SECONDS + 1., 3.],
+        [now - 10., 7.],
+    ];
+    series.compact(now);
+
+    assert_eq!(series.raw, vec![[now - 10., 7.]]);
+    assert_eq!(
+        series.buckets,
+        vec![
+            Bucket::new(now - 2. * RAW_RETENTION, 5.),
+            Bucket {
+                start: now - RAW_RETENTION - 2. * BUCKET_SECONDS,
+                min: 1.,
+                max: 3.,
+                avg: 2.,
+                count: 2,
+            }
+        ]
+    );
+
+    // Buckets are dropped once they are too old
+    series.compact(now + BUCKET_RETENTION);
+    assert_eq!(series.buckets, vec![]);
+}
+
+#[test]
+fn test_history_merging() {
+    let now = 100. * BUCKET_SECONDS;
+    let mut stored = Series::default();
+    stored.raw = vec![
+        [now - RAW_RETENTION - 2. * BUCKET_SECONDS, 1.],
+        [now - 10., 7.],
+    ];
+    stored.compact(now);
+
+    // Persistence data of the same time isn't counted twice
+    let mut series = Series::default();
+    series.raw = vec![[now - 5., 8.]];
+    series.merge(stored);
+    series.merge_raw(vec![
+        [now - RAW_RETENTION - 2. * BUCKET_SECONDS, 1.],
+        [now - 10., 7.],
+    ]);
+    series.compact(now);
+    assert_eq!(
+        series.buckets,
+        vec![Bucket::new(now - RAW_RETENTION - 2. * BUCKET_SECONDS, 1.)]
+    );
+    assert_eq!(series.raw, vec![[now - 10., 7.], [now - 5., 8.]]);
+}
@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+
+use crate::auth::AuthConfig;
+use crate::config::{Item, ItemConfiguration, SmartHomeItem, ViewConfiguration};
+use crate::openhab::OpenHabItem;
+
+/// View for widgets without a location, which is the one shown at start.
+const DEFAULT_VIEW: &str = "floorplan";
+
+/// Keys of the readings shown by the `Climate` widget.
+const CLIMATE_KEYS: &[&str] = &["temperature", "set-temperature", "humidity", "co2"];
+
+/// Configuration being generated, with one view per location.
+struct ConfigBuilder {
+    configuration: ItemConfiguration,
+}
+
+impl ConfigBuilder {
+    fn new() -> Self {
+        ConfigBuilder {
+            configuration: ItemConfiguration {
+                views: HashMap::new(),
+                zooms: HashMap::new(),
+                retry: Default::default(),
+                auth: None,
+            },
+        }
+    }
+
+    /// Add a widget to the given view, creating the view if needed.
+    ///
+    /// Widget names are unique across views, so widgets already added elsewhere are skipped.
+    fn add_widget(&mut self, view: (&str, Option<&str>), widget_name: &str, widget_config: Item) {
+        let views = &mut self.configuration.views;
+        if views
+            .values()
+            .any(|view| view.items.contains_key(widget_name))
+        {
+            return;
+        }
+        let order = views.len() as i32;
+        let (view_name, view_label) = view;
+        views
+            .entry(view_name.to_string())
+            .or_insert_with(|| ViewConfiguration {
+                label: view_label.map(|label| label.to_string()),
+                order,
+                ..Default::default()
+            })
+            .items
+            .insert(widget_name.to_string(), widget_config);
+    }
+}
+
+/// Widget type for the openHAB type of an item, e.g. `Number:Temperature`.
+fn widget_type(ohtype: &str) -> &'static str {
+    match ohtype.split(':').next() {
+        Some("Color") => "Color",
+        Some("Dimmer") => "Dimmer",
+        Some("Switch") => "Switch",
+        Some("Number") => "Number",
+        _ => "Text",
+    }
+}
+
+/// Widget type for the given points and their keys.
+///
+/// Readings of the room climate are shown by `Climate`. Otherwise the most capable control
+/// decides, e.g. a lightbulb with a switch and a dimmer becomes a `Dimmer`.
+fn infer_item_type(points: &[(&OpenHabItem, Option<String>)]) -> String {
+    if points
+        .iter()
+        .any(|(_, key)| matches!(key, Some(key) if CLIMATE_KEYS.contains(&key.as_str())))
+    {
+        return "Climate".to_string();
+    }
+    let types = points
+        .iter()
+        .map(|(point, _)| widget_type(&point.ohtype))
+        .collect::<Vec<_>>();
+    ["Color", "Dimmer", "Switch", "Number"]
+        .into_iter()
+        .find(|t| types.contains(t))
+        .unwrap_or("Text")
+        .to_string()
+}
+
+/// Semantic class of an item, e.g. `Location`, `Equipment` or `Point`.
+fn semantic_class(item: &OpenHabItem) -> Option<&str> {
+    let semantics = item.metadata.semantics.as_ref()?;
+    semantics.value.split('_').next()
+}
+
+fn relation<'a>(item: &'a OpenHabItem, name: &str) -> Option<&'a str> {
+    let semantics = item.metadata.semantics.as_ref()?;
+    semantics.config.get(name).map(|s| s.as_str())
+}
+
+/// Key of a point in its widget, derived from the property it relates to.
+///
+/// Returns `None` for properties widgets don't distinguish, so the item name is used.
+fn point_key(point: &OpenHabItem) -> Option<String> {
+    let semantics = point.metadata.semantics.as_ref()?;
+    let property = relation(point, "relatesTo")?.trim_start_matches("Property_");
+    let key = match property {
+        "Temperature" if semantics.value.starts_with("Point_Setpoint") => "set-temperature",
+        "Temperature" => "temperature",
+        "Humidity" => "humidity",
+        "CO2" => "co2",
+        "Illuminance" => "illuminance",
+        "Power" => "power",
+        "Energy" => "energy",
+        "ColorTemperature" => "colortemp",
+        _ => return None,
+    };
+    Some(key.to_string())
+}
+
+/// Points of an equipment as used by a widget of the given type.
+///
+/// Switch, dimmer and color widgets command the item of the key named like the widget, so
+/// their main control point, the first of the widget's type other than the color
+/// temperature, gets the widget name as key. Switch points of dimmer and color widgets are
+/// dropped, the widget switches the light with its main control point.
+fn control_points<'a>(
+    widget_name: &str,
+    item_type: &str,
+    points: Vec<(&'a OpenHabItem, Option<String>)>,
+) -> Vec<(&'a OpenHabItem, Option<String>)> {
+    if !matches!(item_type, "Switch" | "Dimmer" | "Color") {
+        return points;
+    }
+    let mut has_main_point = false;
+    points
+        .into_iter()
+        .filter(|(point, _)| item_type == "Switch" || widget_type(&point.ohtype) != "Switch")
+        .map(|(point, key)| {
+            let is_main_point = !has_main_point
+                && widget_type(&point.ohtype) == item_type
+                && key.as_deref() != Some("colortemp");
+            if is_main_point {
+                has_main_point = true;
+                return (point, Some(widget_name.to_string()));
+            }
+            (point, key)
+        })
+        .collect()
+}
+
+fn widget_config(
+    label: Option<&str>,
+    item_type: String,
+    points: &[(&str, Option<String>)],
+) -> Item {
+    Item {
+        item_type,
+        label: label.map(|label| label.to_string()),
+        smarthome_items: points
+            .iter()
+            .map(|(name, key)| (name.to_string(), SmartHomeItem { key: key.clone() }))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Location of an item, possibly given by the equipment it's part of.
+fn location<'a>(
+    by_name: &HashMap<&str, &'a OpenHabItem>,
+    item: &'a OpenHabItem,
+) -> Option<&'a str> {
+    let mut item = item;
+    // Bounded, in case of cyclic relations
+    for _ in 0..by_name.len() {
+        if let Some(location) = relation(item, "hasLocation") {
+            return Some(location);
+        }
+        let parent = relation(item, "isPointOf").or(relation(item, "isPartOf"))?;
+        item = *by_name.get(parent)?;
+    }
+    None
+}
+
+/// Name and label of the view for an item, the view of its location if it has one.
+fn view<'a>(
+    by_name: &HashMap<&str, &'a OpenHabItem>,
+    item: &'a OpenHabItem,
+) -> (&'a str, Option<&'a str>) {
+    match location(by_name, item) {
+        Some(location) => (
+            location,
+            by_name
+                .get(location)
+                .and_then(|location| location.label.as_deref()),
+        ),
+        None => (DEFAULT_VIEW, None),
+    }
+}
+
+/// Generate a starter configuration from the items returned by
+/// `/rest/items?metadata=semantics`.
+///
+/// There is a view for each location with a widget for each equipment, combining its points.
+/// Points that are not part of an equipment get their own widget. Widgets without a location
+/// are added to the default view.
+pub fn from_rest_items(items: &[OpenHabItem]) -> ItemConfiguration {
+    let by_name = items
+        .iter()
+        .map(|item| (item.name.as_str(), item))
+        .collect::<HashMap<_, _>>();
+    let is_class = |name: &str, class: &str| {
+        by_name.get(name).and_then(|item| semantic_class(item)) == Some(class)
+    };
+
+    let all_points = items
+        .iter()
+        .filter(|item| semantic_class(item) == Some("Point") && item.ohtype != "Group")
+        .map(|point| (point, point_key(point)))
+        .collect::<Vec<_>>();
+
+    let mut builder = ConfigBuilder::new();
+    for item in items {
+        let (item_type, points) = match semantic_class(item) {
+            Some("Equipment") => {
+                let points = all_points
+                    .iter()
+                    .filter(|(point, _)| relation(point, "isPointOf") == Some(item.name.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if points.is_empty() {
+                    continue;
+                }
+                let item_type = infer_item_type(&points);
+                let points = control_points(&item.name, &item_type, points);
+                (
+                    item_type,
+                    points
+                        .into_iter()
+                        .map(|(point, key)| (point.name.as_str(), key))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            Some("Point") if item.ohtype != "Group" => {
+                let is_point_of_equipment = relation(item, "isPointOf")
+                    .map(|equipment| is_class(equipment, "Equipment"))
+                    .unwrap_or(false);
+                if is_point_of_equipment {
+                    continue;
+                }
+                let key = point_key(item);
+                (
+                    infer_item_type(&[(item, key.clone())]),
+                    vec![(item.name.as_str(), key)],
+                )
+            }
+            _ => continue,
+        };
+        builder.add_widget(
+            view(&by_name, item),
+            &item.name,
+            widget_config(item.label.as_deref(), item_type, &points),
+        );
+    }
+    builder.configuration
+}
+
+/// Widget type for an element of a sitemap, `None` for elements without a widget.
+fn sitemap_widget_type(element: &str) -> Option<&'static str> {
+    match element {
+        "Switch" => Some("Switch"),
+        "Slider" => Some("Dimmer"),
+        "Colorpicker" => Some("Color"),
+        "Setpoint" => Some("Number"),
+        "Text" | "Selection" | "Default" | "Input" => Some("Text"),
+        _ => None,
+    }
+}
+
+/// Value of an attribute of a sitemap element, e.g. `item=Kitchen_Light` or `label="Light"`.
+fn sitemap_attribute<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line
+        .match_indices(&format!("{}=", name))
+        .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))?
+        .0
+        + name.len()
+        + 1;
+    let value = &line[start..];
+    match value.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next(),
+        None => value.split(|c: char| c.is_whitespace() || c == '{').next(),
+    }
+}
+
+/// Name of the view for a frame's label, e.g. `ground_floor` for "Ground Floor".
+fn view_name(label: &str) -> String {
+    label
+        .trim()
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+/// Generate a starter configuration from an openHAB `.sitemap` file.
+///
+/// There is a view for each frame, with a widget for each element showing an item. Elements
+/// outside of frames are added to the default view.
+pub fn from_sitemap(sitemap: &str) -> Result<ItemConfiguration, String> {
+    let mut builder = ConfigBuilder::new();
+    // Label of the frame for each open block, `None` for other blocks
+    let mut blocks: Vec<Option<String>> = vec![];
+
+    for (line_number, line) in sitemap.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with('}') {
+            blocks
+                .pop()
+                .ok_or_else(|| format!("Unexpected }} in line {}", line_number + 1))?;
+            continue;
+        }
+
+        let element = line.split_whitespace().next().unwrap_or_default();
+        // Labels may contain a format, e.g. "Temperature [%.1f °C]"
+        let label = sitemap_attribute(line, "label")
+            .map(|label| label.split('[').next().unwrap_or_default().trim())
+            .filter(|label| !label.is_empty());
+
+        if let (Some(item_type), Some(item_name)) = (
+            sitemap_widget_type(element),
+            sitemap_attribute(line, "item"),
+        ) {
+            let frame = blocks.iter().rev().flatten().next();
+            let view = match frame {
+                Some(frame) => (view_name(frame), Some(frame.as_str())),
+                None => (DEFAULT_VIEW.to_string(), None),
+            };
+            builder.add_widget(
+                (&view.0, view.1),
+                item_name,
+                widget_config(label, item_type.to_string(), &[(item_name, None)]),
+            );
+        }
+
+        if line.ends_with('{') {
+            blocks.push(match element {
+                "Frame" => label.map(|label| label.to_string()),
+                _ => None,
+            });
+        }
+    }
+
+    match blocks.is_empty() {
+        true => Ok(builder.configuration),
+        false => Err("Missing } at the end of the sitemap".to_string()),
+    }
+}
+
+/// Fetch the items with their semantic model from the openHAB instance at the given URL.
+///
+/// An API token can be given in the environment variable `OPENHAB_TOKEN`.
+fn fetch_rest_items(url: &str) -> Result<String, String> {
+    let mut request = ehttp::Request::get(format!(
+        "{}/rest/items?metadata=semantics",
+        url.trim_end_matches('/')
+    ));
+    if let Ok(token) = std::env::var("OPENHAB_TOKEN") {
+        let (name, value) = AuthConfig::Bearer { token }.header();
+        request.headers.insert(name, value);
+    }
+    let response = ehttp::fetch_blocking(&request)?;
+    if !response.ok {
+        return Err(format!(
+            "Request {} failed: {} {}",
+            response.url, response.status, response.status_text
+        ));
+    }
+    response
+        .text()
+        .map(|text| text.to_string())
+        .ok_or_else(|| format!("Response of {} is not UTF-8", response.url))
+}
+
+/// Generate a starter configuration from an openHAB instance, given by its URL, a `.sitemap`
+/// file or a file with the response of `/rest/items?metadata=semantics`.
+pub fn import(source: &str) -> Result<ItemConfiguration, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let items = fetch_rest_items(source)?;
+        return parse_rest_items(&items);
+    }
+    let data =
+        std::fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source, e))?;
+    match source.ends_with(".sitemap") {
+        true => from_sitemap(&data),
+        false => parse_rest_items(&data),
+    }
+}
+
+fn parse_rest_items(data: &str) -> Result<ItemConfiguration, String> {
+    let items: Vec<OpenHabItem> =
+        serde_json::from_str(data).map_err(|e| format!("Failed to parse items: {}", e))?;
+    Ok(from_rest_items(&items))
+}
+
+#[test]
+fn test_from_rest_items() {
+    let configuration =
+        parse_rest_items(include_str!("../fixtures/openhab/rest_items.json")).unwrap();
+
+    let mut views = configuration.views.keys().collect::<Vec<_>>();
+    views.sort();
+    assert_eq!(views, vec!["Bathroom", "Kitchen", "floorplan"]);
+
+    let kitchen = &configuration.views["Kitchen"];
+    assert_eq!(kitchen.label.as_deref(), Some("Kitchen"));
+    let light = &kitchen.items["Kitchen_Light"];
+    assert_eq!(light.item_type, "Dimmer");
+    assert_eq!(light.label.as_deref(), Some("Ceiling light"));
+    // The switch point is dropped, the dimmer switches the light
+    assert_eq!(light.smarthome_items.len(), 2);
+    assert_eq!(
+        light.smarthome_items["Kitchen_Light_Brightness"].key,
+        Some("Kitchen_Light".to_string())
+    );
+    assert_eq!(
+        light.smarthome_items["Kitchen_Light_ColorTemperature"].key,
+        Some("colortemp".to_string())
+    );
+    // Points directly in a location get their own widget
+    assert_eq!(kitchen.items["Kitchen_Window"].item_type, "Text");
+
+    let thermostat = &configuration.views["Bathroom"].items["Bathroom_Thermostat"];
+    assert_eq!(thermostat.item_type, "Climate");
+    assert_eq!(
+        thermostat.smarthome_items["Bathroom_Setpoint"].key,
+        Some("set-temperature".to_string())
+    );
+
+    // Equipment without location
+    let pump = &configuration.views["floorplan"].items["Garden_Pump"];
+    assert_eq!(pump.item_type, "Switch");
+    assert_eq!(
+        pump.smarthome_items["Garden_Pump_Switch"].key,
+        Some("Garden_Pump".to_string())
+    );
+}
+
+#[test]
+fn test_imported_widgets_send_commands() {
+    use crate::device_model::{DeviceModel, SceneInteraction};
+
+    let configuration =
+        parse_rest_items(include_str!("../fixtures/openhab/rest_items.json")).unwrap();
+    let mut device_model = DeviceModel::default();
+    device_model.parse(&configuration);
+
+    for (widget_name, item_name, value) in [
+        ("Kitchen_Light", "Kitchen_Light_Brightness", "100"),
+        ("Garden_Pump", "Garden_Pump_Switch", "ON"),
+    ] {
+        let requests = device_model
+            .scene_interaction(widget_name, SceneInteraction::Click)
+            .unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].item, item_name);
+        assert_eq!(requests[0].value, value);
+    }
+}
+
+#[test]
+fn test_from_sitemap() {
+    let configuration = from_sitemap(include_str!("../fixtures/openhab/home.sitemap")).unwrap();
+
+    let mut views = configuration.views.keys().collect::<Vec<_>>();
+    views.sort();
+    assert_eq!(views, vec!["floorplan", "ground_floor", "outside"]);
+
+    let ground_floor = &configuration.views["ground_floor"];
+    assert_eq!(ground_floor.label.as_deref(), Some("Ground Floor"));
+    assert_eq!(ground_floor.items["Kitchen_Light"].item_type, "Dimmer");
+    assert_eq!(
+        ground_floor.items["Kitchen_Temperature"].label.as_deref(),
+        Some("Temperature")
+    );
+    // Items shown twice are only added once
+    assert!(!configuration.views["outside"]
+        .items
+        .contains_key("Kitchen_Light"));
+    assert_eq!(
+        configuration.views["floorplan"].items["Presence"].item_type,
+        "Switch"
+    );
+
+    assert!(from_sitemap("sitemap home {\n Frame {\n}").is_err());
+}
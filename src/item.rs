@@ -1,11 +1,18 @@
 use crate::config::SmartHomeItemInternal;
-use crate::events::SceneModificationEvent;
-use crate::openhab::WidgetInteraction;
+use crate::events::{LightModification, SceneModificationEvent};
+use crate::item::generic_item::INTERNAL_STATE_PREFIX;
+use crate::openhab::{RequestedStateChangeFromWidget, WidgetInteraction};
 use crate::plot::PlotType;
+use crate::ui;
 use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 
+use crate::utils;
+
 use bevy::log::{debug, error};
 use bevy_egui::egui;
+use chrono::{DateTime, Local, Utc};
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -22,10 +29,22 @@ pub mod light_auto;
 pub mod robot;
 pub mod switch;
 
+/// Status of a state change requested by a widget, until the backend confirms it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ChangeStatus {
+    /// The widget already shows the requested state.
+    Pending,
+    Confirmed,
+    /// The change failed or wasn't confirmed in time, the previous state has been restored.
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct NotificationStatus {
     pub color: String, // Any color string - will be passed as is to the template
     pub priority: u8,  // A priority as integer. 0 means nothing, higher means higher priority
     pub num: u32,      // Number of notifications for this configuration
+    pub label: String, // What is notified about, e.g. "lights on". Notifications are grouped by it
 }
 
 pub(crate) trait Item {
@@ -35,8 +54,25 @@ pub(crate) trait Item {
         self.state_changed(state_key, new_state);
     }
 
-    fn set_configuration(&mut self, config: &serde_json::Value) {
+    /// Whether requested changes of the given key are shown right away, before the backend
+    /// confirms them.
+    ///
+    /// Widgets opting in are told about the status of the change. By default, widgets only show
+    /// the states reported by the backend.
+    fn is_optimistic(&self, _state_key: &str) -> bool {
+        false
+    }
+
+    /// A state change of the given key requested by this widget is pending, confirmed or failed.
+    ///
+    /// Called after the widget has been passed the requested state, or the state restored after a
+    /// failure. By default, the status is not shown.
+    fn change_status(&mut self, _state_key: &str, _status: ChangeStatus) {}
+
+    /// Pass the widget's `templateConf`, failing if it doesn't match the widget's configuration.
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
         debug!("Ignoring configuration {:?}", config);
+        Ok(())
     }
 
     fn set_smarthome_items(&mut self, _config: &HashMap<String, SmartHomeItemInternal>) {}
@@ -49,6 +85,11 @@ pub(crate) trait Item {
         None
     }
 
+    /// State of the widget as key value pairs, used to render slider cards.
+    fn state_as_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        serde_json::Map::new()
+    }
+
     fn get_plot_type(&self, _key: &str) -> PlotType {
         return PlotType::LinePlot;
     }
@@ -76,23 +117,80 @@ pub(crate) trait Item {
         vec![]
     }
 
+    /// Scene modifications previewing the effect of an interaction, e.g. of activating a scene.
+    ///
+    /// Returns `None` if nothing is previewed. By default, widgets don't preview anything.
+    fn scene_preview(&self) -> Option<Vec<SceneModificationEvent>> {
+        None
+    }
+
+    /// Stop previewing, e.g. because the widget is no longer rendered.
+    ///
+    /// By default, widgets don't preview anything.
+    fn end_preview(&mut self) {}
+
+    /// Interaction triggered by clicking one of the widget's entities in the 3D scene.
+    ///
+    /// By default, clicking the scene does nothing.
+    fn scene_clicked(&self, _widget_name: &str) -> Vec<WidgetInteraction> {
+        vec![]
+    }
+
+    /// State the widget keeps across restarts, e.g. values it accumulated or learned.
+    ///
+    /// The state is stored next to the local history. By default, widgets don't keep any state.
+    fn stored_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Restore the state returned by [`Item::stored_state`] in a previous run.
+    fn restore_state(&mut self, _state: &serde_json::Value) {}
+
+    /// Keys whose past values the widget learns from, see [`Item::history_received`].
+    fn history_keys(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    /// Past values of one of the [`Item::history_keys`], as timestamp and value, oldest first.
+    ///
+    /// Called with the local history once it's loaded, and with the values returned by the
+    /// backend's persistence service.
+    fn history_received(&mut self, _state_key: &str, _datapoints: &[[f64; 2]]) {}
+
     /// Render the fullscreen view of this widget, if enabled.
+    ///
+    /// By default, this is an empty window that can only be closed.
     fn render_fullscreen(
         &self,
-        _widget_render_setting: &WidgetRenderSetting,
+        widget_render_setting: &WidgetRenderSetting,
         ctx: &mut egui::Context,
     ) -> Vec<WidgetInteraction> {
-        let mut requests = vec![];
-        egui::Window::new("Fullscreen").show(ctx, |ui| {
-            if ui.add(egui::Button::new("Close")).clicked() {
-                requests.push(WidgetInteraction::FullscreenRequest(false));
-            }
-        });
-
-        requests
+        ui::render_fullscreen_window(ctx, widget_render_setting, |_| vec![])
     }
 }
 
+/// Request toggling a widget with a simple on/off state.
+///
+/// Commands default to ON and OFF, same as for the buttons rendered by the widgets.
+pub(crate) fn toggle_interaction(
+    widget_name: &str,
+    state: bool,
+    on_command: Option<&str>,
+    off_command: Option<&str>,
+) -> Vec<WidgetInteraction> {
+    let value = if state {
+        off_command.unwrap_or("OFF")
+    } else {
+        on_command.unwrap_or("ON")
+    };
+    vec![WidgetInteraction::StateChange(
+        RequestedStateChangeFromWidget {
+            key: widget_name.to_string(),
+            value: value.to_string(),
+        },
+    )]
+}
+
 pub struct Number {
     value: f64,
 }
@@ -150,6 +248,7 @@ impl Item for Blind {
                 color: "green".to_string(),
                 priority: 1,
                 num: 1,
+                label: "blinds closed".to_string(),
             })
         } else {
             None
@@ -161,41 +260,141 @@ impl Item for Blind {
     }
 }
 
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextConfig {
+    /// Pattern to format the state with, e.g. `%.1f °C`. See [`utils::format_state`].
+    format: Option<String>,
+}
+
 pub struct Text {
     value: Option<String>,
+    config: Option<TextConfig>,
 }
 
 impl Text {
     pub fn new() -> Text {
-        Text { value: None }
+        Text {
+            value: None,
+            config: None,
+        }
+    }
+
+    /// The value, formatted with the configured pattern if any.
+    fn formatted(&self) -> Option<String> {
+        let value = self.value.as_ref()?;
+        match self.config.as_ref().and_then(|c| c.format.as_ref()) {
+            Some(format) => Some(utils::format_state(format, value)),
+            None => Some(value.to_string()),
+        }
     }
 }
 
 impl Item for Text {
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        self.config = Some(serde_json::from_value(config.clone())?);
+        Ok(())
+    }
+
     fn state_changed(&mut self, _: &str, new_state: &str) {
         self.value = Some(new_state.to_string())
     }
+
+    fn state_as_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = serde_json::Map::new();
+        if let Some(value) = self.formatted() {
+            map.insert("value".to_string(), serde_json::Value::String(value));
+        }
+        map
+    }
+
+    /// Info card with the formatted value.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let value = self.formatted().unwrap_or("n.a.".to_string());
+        ui::render_info_card(context, render_setting, render_position, |egui_ui| {
+            egui_ui.strong(value);
+        })
+    }
+}
+
+// Number of upcoming events shown on the calendar card.
+const MAX_CALENDAR_EVENTS: usize = 3;
+
+/// Parse a date time as sent by openHAB, e.g. `2024-05-01T10:00:00.000+0200`.
+fn parse_date_time(state: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(state)
+        .or_else(|_| DateTime::parse_from_str(state, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
 }
 
+/// Calendar with upcoming events.
+///
+/// Events are given by the keys `event` and `time`. More events can be added with a suffix,
+/// e.g. `event_2` and `time_2`.
 pub struct Calendar {
-    event: Option<String>,
-    time: Option<String>,
+    /// Title and start time of each event, by suffix of their keys.
+    events: HashMap<String, (Option<String>, Option<DateTime<Utc>>)>,
 }
 
 impl Calendar {
     pub fn new() -> Calendar {
         Calendar {
-            event: None,
-            time: None,
+            events: HashMap::new(),
         }
     }
+
+    /// Upcoming events with their start time, the next one first.
+    fn upcoming(&self, now: DateTime<Utc>) -> Vec<(&str, DateTime<Utc>)> {
+        let mut upcoming = self
+            .events
+            .values()
+            .filter_map(|(event, time)| match (event, time) {
+                (Some(event), Some(time)) if *time >= now => Some((event.as_str(), *time)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        upcoming.sort_by_key(|(_, time)| *time);
+        upcoming
+    }
+}
+
+/// Time until the given event, e.g. "in 2 hours".
+fn countdown(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    HumanTime::from(time - now).to_text_en(Accuracy::Rough, Tense::Future)
+}
+
+#[test]
+fn test_calendar() {
+    let now = parse_date_time("2024-05-01T10:00:00.000+0200").unwrap();
+    let mut calendar = Calendar::new();
+    calendar.state_changed("event", "Dentist");
+    calendar.state_changed("time", "2024-05-01T12:00:00.000+0200");
+    calendar.state_changed("event_2", "Breakfast");
+    calendar.state_changed("time_2", "2024-05-01T08:00:00+02:00");
+    calendar.state_changed("event_3", "Meeting");
+    calendar.state_changed("time_3", "2024-05-01T11:00:00.000+0200");
+
+    let upcoming = calendar.upcoming(now);
+    assert_eq!(
+        upcoming.iter().map(|(e, _)| *e).collect::<Vec<&str>>(),
+        vec!["Meeting", "Dentist"]
+    );
+    assert_eq!(countdown(upcoming[1].1, now), "in 2 hours");
 }
 
 impl Item for Calendar {
     fn state_changed(&mut self, state_key: &str, new_state: &str) {
-        match state_key {
-            "event" => self.event = Some(new_state.to_string()),
-            "time" => self.time = Some(new_state.to_string()),
+        let (key, suffix) = state_key.split_once('_').unwrap_or((state_key, ""));
+        let event = self.events.entry(suffix.to_string()).or_default();
+        match key {
+            "event" => event.0 = Some(new_state.to_string()).filter(|s| s != "NULL"),
+            "time" => event.1 = parse_date_time(new_state),
             _ => {
                 error!(
                     "calendar: could not parse state_changed for key {} and state {}",
@@ -204,14 +403,78 @@ impl Item for Calendar {
             }
         }
     }
+
+    /// Card with the next events and the time until they start.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let now = Utc::now();
+        let upcoming = self.upcoming(now);
+        ui::render_info_card(context, render_setting, render_position, |egui_ui| {
+            if upcoming.is_empty() {
+                egui_ui.label("📅 No upcoming events");
+            }
+            for (event, time) in upcoming.iter().take(MAX_CALENDAR_EVENTS) {
+                egui_ui.horizontal(|egui_ui| {
+                    egui_ui.strong(format!("📅 {}", event));
+                    egui_ui.label(egui::RichText::new(countdown(*time, now)).small());
+                });
+            }
+        })
+    }
+
+    /// All upcoming events with their start time.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        ctx: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let now = Utc::now();
+        ui::render_fullscreen_window(ctx, render_setting, |egui_ui| {
+            egui::Grid::new(render_setting.id.with("events"))
+                .striped(true)
+                .show(egui_ui, |egui_ui| {
+                    for (event, time) in self.upcoming(now) {
+                        egui_ui.label(event);
+                        egui_ui.label(
+                            time.with_timezone(&Local)
+                                .format("%a %d.%m. %H:%M")
+                                .to_string(),
+                        );
+                        egui_ui.label(countdown(time, now));
+                        egui_ui.end_row();
+                    }
+                });
+            vec![]
+        })
+    }
 }
 
-#[derive(Deserialize, Clone, Debug)]
+// Keys of the items used to control the player.
+const KEY_MUSIC_CONTROLLER: &str = "controller";
+const KEY_MUSIC_VOLUME: &str = "volume";
+const KEY_MUSIC_RADIO: &str = "radio";
+const KEY_MUSIC_PLAYLIST: &str = "playlist";
+// Adds the zone with the given id to the group of this zone.
+const KEY_MUSIC_ADD: &str = "add";
+// Removes this zone from its group.
+const KEY_MUSIC_STANDALONE: &str = "standalone";
+
+const COVER_SIZE: f32 = 200.;
+
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct MusicConfig {
+    /// Label and id of all players, used to show and change grouping.
     devices: Vec<[String; 2]>,
+    /// Id of this player.
     id: String,
+    /// Label and value sent to the `radio` key.
     radios: Vec<[String; 2]>,
+    /// Label and value sent to the `playlist` key.
     playlists: Vec<[String; 2]>,
 }
 
@@ -276,10 +539,49 @@ impl Music {
     //     }
     // }
 
+    /// Whether this zone plays on its own or coordinates a group.
     fn is_standalone(&self) -> bool {
         self.config.as_ref().map(|x| x.id.to_string()) == self.coordinator
     }
 
+    /// Label of the player with the given id, the id itself if it's not configured.
+    fn device_label<'a>(&'a self, id: &'a str) -> &'a str {
+        self.config
+            .iter()
+            .flat_map(|config| config.devices.iter())
+            .find(|[_, device_id]| device_id == id)
+            .map(|[label, _]| label.as_str())
+            .unwrap_or(id)
+    }
+
+    fn transport_interaction(&self) -> WidgetInteraction {
+        let command = if self.is_stopped() { "PLAY" } else { "PAUSE" };
+        WidgetInteraction::state_change(KEY_MUSIC_CONTROLLER, command)
+    }
+
+    /// Picker for one of the configured radios or playlists.
+    fn render_picker(
+        egui_ui: &mut egui::Ui,
+        id: egui::Id,
+        label: &str,
+        key: &str,
+        choices: &[[String; 2]],
+        requests: &mut Vec<WidgetInteraction>,
+    ) {
+        if choices.is_empty() {
+            return;
+        }
+        egui::ComboBox::from_id_source(id)
+            .selected_text(label)
+            .show_ui(egui_ui, |egui_ui| {
+                for [choice_label, value] in choices {
+                    if egui_ui.selectable_label(false, choice_label).clicked() {
+                        requests.push(WidgetInteraction::state_change(key, value));
+                    }
+                }
+            });
+    }
+
     fn is_stopped(&self) -> bool {
         match &self.state {
             None => true,
@@ -289,11 +591,11 @@ impl Music {
 }
 
 impl Item for Music {
-    fn set_configuration(&mut self, config: &serde_json::Value) {
-        let config: MusicConfig =
-            serde_json::from_value(config.clone()).expect("Failed to parse config for item");
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        let config: MusicConfig = serde_json::from_value(config.clone())?;
         debug!("Music: using configuration {:?}", &config);
         self.config = Some(config);
+        Ok(())
     }
 
     fn state_changed(&mut self, state_key: &str, new_state: &str) {
@@ -358,6 +660,174 @@ impl Item for Music {
         // }
     }
 
+    /// Speaker icon in the room of the player, toggling playback.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let mut requests = vec![];
+        egui::Area::new(render_setting.id)
+            .fixed_pos(egui::pos2(render_position.0, render_position.1))
+            .show(context, |egui_ui| {
+                egui_ui.horizontal(|egui_ui| {
+                    let icon = if self.is_stopped() { "🔈" } else { "🔊" };
+                    let response = egui_ui.button(icon);
+                    if response.clicked() {
+                        requests.push(self.transport_interaction());
+                    }
+                    if response.secondary_clicked() {
+                        requests.push(WidgetInteraction::FullscreenRequest(true));
+                    }
+                    if !self.is_standalone() {
+                        if let Some(coordinator) = &self.coordinator {
+                            egui_ui.label(
+                                egui::RichText::new(format!(
+                                    "⛓ {}",
+                                    self.device_label(coordinator)
+                                ))
+                                .small(),
+                            );
+                        }
+                    }
+                });
+            });
+        requests
+    }
+
+    /// Cover art and current track with transport, volume, favorites and grouping controls.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        ctx: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        ui::render_fullscreen_window(ctx, render_setting, |egui_ui| {
+            let mut requests = vec![];
+
+            if let Some(image) = &self.image {
+                egui_ui.add(
+                    egui::Image::new(image)
+                        .max_width(COVER_SIZE)
+                        .max_height(COVER_SIZE),
+                );
+            }
+            if let Some(title) = &self.title {
+                egui_ui.heading(title);
+            }
+            if let Some(artist) = &self.artist {
+                egui_ui.label(artist);
+            }
+
+            egui_ui.horizontal(|egui_ui| {
+                if egui_ui.button("⏮").clicked() {
+                    requests.push(WidgetInteraction::state_change(
+                        KEY_MUSIC_CONTROLLER,
+                        "PREVIOUS",
+                    ));
+                }
+                let icon = if self.is_stopped() { "▶" } else { "⏸" };
+                if egui_ui.button(icon).clicked() {
+                    requests.push(self.transport_interaction());
+                }
+                if egui_ui.button("⏭").clicked() {
+                    requests.push(WidgetInteraction::state_change(
+                        KEY_MUSIC_CONTROLLER,
+                        "NEXT",
+                    ));
+                }
+            });
+
+            if let Some(volume) = ui::render_state_slider(
+                egui_ui,
+                render_setting.id.with("volume"),
+                self.volume.unwrap_or(0) as f32,
+                0.0..=100.0,
+                "Volume",
+            ) {
+                requests.push(WidgetInteraction::state_change(
+                    KEY_MUSIC_VOLUME,
+                    &format!("{:.0}", volume),
+                ));
+            }
+
+            let Some(config) = &self.config else {
+                return requests;
+            };
+
+            egui_ui.horizontal(|egui_ui| {
+                Music::render_picker(
+                    egui_ui,
+                    render_setting.id.with("radio"),
+                    "Radio",
+                    KEY_MUSIC_RADIO,
+                    &config.radios,
+                    &mut requests,
+                );
+                Music::render_picker(
+                    egui_ui,
+                    render_setting.id.with("playlist"),
+                    "Playlist",
+                    KEY_MUSIC_PLAYLIST,
+                    &config.playlists,
+                    &mut requests,
+                );
+            });
+
+            egui_ui.separator();
+            match (&self.coordinator, self.is_standalone()) {
+                (Some(coordinator), false) => {
+                    egui_ui.label(format!("Grouped with {}", self.device_label(coordinator)));
+                    if egui_ui.button("Leave group").clicked() {
+                        requests.push(WidgetInteraction::state_change(KEY_MUSIC_STANDALONE, "ON"));
+                    }
+                }
+                _ => {
+                    egui_ui.label("Add to this group:");
+                    egui_ui.horizontal_wrapped(|egui_ui| {
+                        for [label, id] in &config.devices {
+                            if id != &config.id && egui_ui.button(label).clicked() {
+                                requests.push(WidgetInteraction::state_change(KEY_MUSIC_ADD, id));
+                            }
+                        }
+                    });
+                }
+            }
+
+            requests
+        })
+    }
+
+    fn state_as_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = serde_json::Map::new();
+        let values = [
+            ("artist", &self.artist),
+            ("title", &self.title),
+            ("track", &self.track),
+            ("image", &self.image),
+            ("state", &self.state),
+            ("zonename", &self.zonename),
+            ("zonegroupid", &self.zonegroupid),
+            ("coordinator", &self.coordinator),
+        ];
+        for (key, value) in values {
+            if let Some(value) = value {
+                map.insert(
+                    key.to_string(),
+                    serde_json::Value::String(value.to_string()),
+                );
+            }
+        }
+        if let Some(volume) = self.volume {
+            map.insert("volume".to_string(), serde_json::Value::from(volume));
+        }
+        map.insert(
+            "is_standalone".to_string(),
+            serde_json::Value::Bool(self.is_standalone()),
+        );
+        map
+    }
+
     fn render_slider(&self) -> Option<(String, usize)> {
         if self.is_standalone() && !self.is_stopped() && self.image.is_some() {
             Some(("slider-music".to_string(), 100))
@@ -367,30 +837,123 @@ impl Item for Music {
     }
 }
 
-#[derive(Deserialize, Clone, Debug)]
+/// Key of the internal state holding the command of the scene being previewed.
+fn scene_preview_key() -> String {
+    format!("{}preview", INTERNAL_STATE_PREFIX)
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SceneConfig {
+    /// Command sent to the backend, label and icon of each scene.
     scenes: Vec<[String; 3]>,
+    /// Brightness in percent of the lights in each scene, by command of the scene and name of
+    /// the light. Used to preview scenes.
+    #[serde(default)]
+    lights: HashMap<String, HashMap<EntityName, f32>>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Scene {
     scenes: Option<SceneConfig>,
+    /// Command of the active scene as reported by the backend.
+    state: Option<String>,
+    /// Command of the scene being previewed.
+    preview: Option<String>,
 }
 
 impl Scene {
     pub fn new() -> Self {
-        Self { scenes: None }
+        Self {
+            scenes: None,
+            state: None,
+            preview: None,
+        }
     }
 }
 
 impl Item for Scene {
-    fn set_configuration(&mut self, config: &serde_json::Value) {
-        self.scenes =
-            Some(serde_json::from_value(config.clone()).expect("Failed to parse config for Scene"));
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        self.scenes = Some(serde_json::from_value(config.clone())?);
         debug!("Scene: using configuration {:?}", self.scenes);
+        Ok(())
+    }
+
+    fn state_changed(&mut self, state_key: &str, new_state: &str) {
+        let value = Some(new_state.to_string()).filter(|s| !s.is_empty() && s != "NULL");
+        if state_key == scene_preview_key() {
+            self.preview = value;
+        } else {
+            self.state = value;
+        }
     }
 
-    fn state_changed(&mut self, _: &str, _new_state: &str) {}
+    fn end_preview(&mut self) {
+        self.preview = None;
+    }
+
+    /// Lights of the scene being previewed at the scene's brightness.
+    fn scene_preview(&self) -> Option<Vec<SceneModificationEvent>> {
+        let lights = self.scenes.as_ref()?.lights.get(self.preview.as_ref()?)?;
+        Some(
+            lights
+                .iter()
+                .map(|(entity_name, brightness)| {
+                    SceneModificationEvent::LightModification(LightModification {
+                        entity_name: entity_name.to_string(),
+                        illuminance_percentage: *brightness,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Scene picker with a button per scene, the active scene is highlighted.
+    ///
+    /// Hovering a button previews the scene in 3D, clicking it activates the scene.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let mut requests = vec![];
+        let Some(config) = &self.scenes else {
+            return requests;
+        };
+
+        let mut hovered = None;
+        egui::Area::new(render_setting.id)
+            .fixed_pos(egui::pos2(render_position.0, render_position.1))
+            .show(context, |egui_ui| {
+                egui_ui.horizontal(|egui_ui| {
+                    if let Some(label) = &render_setting.label {
+                        egui_ui.label(label);
+                    }
+                    for [command, label, icon] in &config.scenes {
+                        let is_active = self.state.as_ref() == Some(command);
+                        let response =
+                            egui_ui.selectable_label(is_active, format!("{} {}", icon, label));
+                        if response.clicked() {
+                            requests.push(WidgetInteraction::state_change(
+                                &render_setting.widget_name,
+                                command,
+                            ));
+                        }
+                        if response.hovered() {
+                            hovered = Some(command.to_string());
+                        }
+                    }
+                });
+            });
+
+        if hovered != self.preview {
+            requests.push(WidgetInteraction::state_change(
+                &scene_preview_key(),
+                &hovered.unwrap_or_default(),
+            ));
+        }
+        requests
+    }
 }
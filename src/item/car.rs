@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+
+use bevy::log::error;
+use bevy::math::Vec3;
+use bevy_egui::egui;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -5,17 +11,34 @@ use chrono::offset::Utc;
 use chrono::{DateTime, ParseError};
 use chrono_humanize::{Accuracy, HumanTime, Tense};
 
+use crate::events::{EnergyFlowModification, SceneModificationEvent, VisibilityModification};
 use crate::item::generic_item::{generic_translate_value, GenericItem};
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
+
+const KEY_SOC: &str = "soc";
+const KEY_RANGE: &str = "range";
+const KEY_CHARGING_POWER: &str = "charging_power";
+const KEY_PLUG: &str = "plug";
+const KEY_HOME: &str = "home";
+const KEY_EVENTSTAMP: &str = "eventstamp";
+const KEY_ODOMETER: &str = "odometer";
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+const IMAGE_WIDTH: f32 = 300.;
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CarConfig {
     pub image: String,
+    /// Polylines for the charging animation, given as list of points in the 3D scene.
+    #[serde(default)]
+    pub paths: HashMap<String, Vec<[f32; 3]>>,
 }
 
 fn translate_value(key: &str, input: &str) -> Value {
     match key {
-        "odometer" => Value::String(
+        KEY_ODOMETER => Value::String(
             input
                 .replace(" m", "")
                 .parse::<f32>()
@@ -23,7 +46,7 @@ fn translate_value(key: &str, input: &str) -> Value {
                 .map_err(|e| e.to_string())
                 .unwrap_or(input.to_string()),
         ),
-        "eventstamp" => Value::String(
+        KEY_EVENTSTAMP => Value::String(
             input
                 .parse::<DateTime<Utc>>()
                 .map(|d| HumanTime::from(Utc::now() - d).to_text_en(Accuracy::Rough, Tense::Past))
@@ -34,11 +57,48 @@ fn translate_value(key: &str, input: &str) -> Value {
     }
 }
 
+/// Whether a switch-like state is on, e.g. `ON`, `CONNECTED` or `home`.
+fn is_on(generic_item: &GenericItem<CarConfig>, key: &str) -> bool {
+    match generic_item.state.get(key) {
+        Some(state) => matches!(
+            state.0.to_lowercase().as_str(),
+            "on" | "true" | "connected" | "home" | "open"
+        ),
+        None => false,
+    }
+}
+
+/// Charging power in W. The state may be given in W or kW.
+fn charging_power(generic_item: &GenericItem<CarConfig>) -> f32 {
+    let Some(state) = generic_item.state.get(KEY_CHARGING_POWER) else {
+        return 0.;
+    };
+    let mut tokens = state.0.split(' ');
+    let value = tokens.next().and_then(|v| v.parse::<f32>().ok());
+    match (value, tokens.next()) {
+        (Some(value), Some("kW")) => value * 1000.,
+        (Some(value), _) => value,
+        (None, _) => 0.,
+    }
+}
+
 fn init_map(generic_item: &GenericItem<CarConfig>) -> serde_json::Map<String, Value> {
     let mut map = serde_json::Map::new();
     if let Some(config) = &generic_item.config {
         map.insert("image".to_string(), Value::String(config.image.to_string()));
     }
+    map.insert(
+        "is_home".to_string(),
+        Value::Bool(is_on(generic_item, KEY_HOME)),
+    );
+    map.insert(
+        "is_plugged".to_string(),
+        Value::Bool(is_on(generic_item, KEY_PLUG)),
+    );
+    map.insert(
+        "is_charging".to_string(),
+        Value::Bool(charging_power(generic_item) > 0.),
+    );
     map
 }
 
@@ -46,6 +106,95 @@ fn render_slider(_generic_item: &GenericItem<CarConfig>) -> Option<(String, usiz
     Some(("slider-car".to_string(), 10))
 }
 
+/// Show the car in the garage while it's home and animate charging.
+fn state_to_blender(
+    generic_item: &GenericItem<CarConfig>,
+    entity_name: &EntityName,
+    modification: SceneModification,
+) -> Vec<SceneModificationEvent> {
+    match modification {
+        SceneModification::Visibility() => vec![SceneModificationEvent::VisibilityModification(
+            VisibilityModification {
+                entity_name: entity_name.to_string(),
+                visible: is_on(generic_item, KEY_HOME),
+            },
+        )],
+        SceneModification::Flow() => {
+            let points = generic_item
+                .config
+                .as_ref()
+                .and_then(|config| config.paths.get(entity_name))
+                .map(|points| points.iter().map(|p| Vec3::from(*p)).collect());
+            vec![SceneModificationEvent::EnergyFlowModification(
+                EnergyFlowModification {
+                    path_name: entity_name.to_string(),
+                    points,
+                    power: charging_power(generic_item),
+                },
+            )]
+        }
+        _ => {
+            error!("Unsupported modification {:?} in Car", modification);
+            vec![]
+        }
+    }
+}
+
+/// Image of the car with its charging state, range and when it was last seen.
+fn render_fullscreen(
+    generic_item: &GenericItem<CarConfig>,
+    render_setting: &WidgetRenderSetting,
+    context: &mut egui::Context,
+) -> Vec<WidgetInteraction> {
+    let get = |key: &str| {
+        generic_item
+            .state
+            .get(key)
+            .map(|s| match translate_value(key, &s.0) {
+                Value::String(s) => s,
+                value => value.to_string(),
+            })
+            .unwrap_or("n.a.".to_string())
+    };
+    let power = charging_power(generic_item);
+
+    ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+        if let Some(config) = &generic_item.config {
+            egui_ui.add(egui::Image::new(&config.image).max_width(IMAGE_WIDTH));
+        }
+        egui::Grid::new(render_setting.id.with("state"))
+            .striped(true)
+            .show(egui_ui, |egui_ui| {
+                let plug = match (is_on(generic_item, KEY_PLUG), power > 0.) {
+                    (true, true) => format!("charging with {:.1} kW", power / 1000.),
+                    (true, false) => "plugged in".to_string(),
+                    (false, _) => "unplugged".to_string(),
+                };
+                let location = match is_on(generic_item, KEY_HOME) {
+                    true => "home",
+                    false => "away",
+                };
+                let rows = [
+                    ("State of charge", format!("{} %", get(KEY_SOC))),
+                    ("Range", get(KEY_RANGE)),
+                    ("Charging", plug),
+                    ("Location", location.to_string()),
+                    ("Odometer", get(KEY_ODOMETER)),
+                    ("Last seen", get(KEY_EVENTSTAMP)),
+                ];
+                for (label, value) in rows {
+                    egui_ui.label(label);
+                    egui_ui.label(value);
+                    egui_ui.end_row();
+                }
+            });
+        vec![]
+    })
+}
+
 pub(crate) fn new() -> GenericItem<CarConfig> {
-    GenericItem::with_custom_functions(translate_value, init_map, render_slider)
+    let mut d = GenericItem::with_custom_functions(translate_value, init_map, render_slider);
+    d.with_blender_f(state_to_blender);
+    d.with_fullscreen_f(render_fullscreen);
+    d
 }
@@ -1,23 +1,124 @@
 use crate::config::SmartHomeItemInternal;
-use crate::events::{SceneModificationEvent, SunModification};
-use crate::widget_settings::{EntityName, SceneModification};
+use crate::events::{SceneModificationEvent, SunModification, TintModification};
+use crate::history::parse_value;
+use crate::item::NotificationStatus;
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 
 use instant::Instant;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::time::Duration;
 
 use bevy::log::{error, info};
+use bevy::prelude::Color;
+use bevy_egui::egui::{self, RichText};
 
 use crate::item::Item;
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-struct ClimateConfig {
+pub(crate) struct ClimateConfig {
+    #[serde(default)]
     sun_mapping: Vec<(f32, f32)>,
+    #[serde(default)]
     world_mapping: Vec<(f32, f32)>,
+    #[serde(default)]
     environment: HashMap<String, Vec<(f32, f32)>>,
+    /// Tint of the room's floor meshes, which are the blender items with the `Highlight`
+    /// modification.
+    overlay: Option<OverlayConfig>,
+    /// Readings raising a notification when out of range.
+    #[serde(default)]
+    thresholds: Vec<ThresholdConfig>,
+    /// Step of the thermostat's +/- buttons in °C. Defaults to 0.5 °C.
+    setpoint_step: Option<f64>,
+    /// Lowest and highest setpoint of the thermostat in °C. Defaults to 5 °C to 30 °C.
+    setpoint_range: Option<(f64, f64)>,
 }
+
+// Key of the thermostat's setpoint.
+const KEY_SET_TEMPERATURE: &str = "set-temperature";
+const DEFAULT_SETPOINT_STEP: f64 = 0.5;
+const DEFAULT_SETPOINT_RANGE: (f64, f64) = (5., 30.);
+
+/// Color scale for tinting floors by one of the readings.
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OverlayConfig {
+    /// Key of the reading, e.g. `temperature` or `co2`.
+    reading: String,
+    /// Colors as RGB for values of the reading, interpolated in between. Defaults to a scale
+    /// for the reading.
+    #[serde(default)]
+    scale: Vec<(f32, [f32; 3])>,
+}
+
+impl OverlayConfig {
+    fn scale(&self) -> Vec<(f32, [f32; 3])> {
+        if !self.scale.is_empty() {
+            return self.scale.clone();
+        }
+        match self.reading.as_str() {
+            "co2" => vec![
+                (400., [0., 0.3, 0.]),
+                (1000., [0.3, 0.3, 0.]),
+                (1600., [0.4, 0., 0.]),
+            ],
+            _ => vec![
+                (16., [0., 0., 0.4]),
+                (21., [0., 0.3, 0.]),
+                (26., [0.4, 0., 0.]),
+            ],
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ThresholdConfig {
+    /// Key of the reading, e.g. `co2`.
+    reading: String,
+    above: Option<f64>,
+    below: Option<f64>,
+    /// What is notified about, e.g. "bad air".
+    label: String,
+}
+
+/// Color of the scale for the given value, interpolated between the two closest colors.
+fn color_on_scale(value: f32, scale: &[(f32, [f32; 3])]) -> Option<Color> {
+    let mut scale = scale.to_vec();
+    scale.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first, last) = (scale.first()?, scale.last()?);
+    let [r, g, b] = if value <= first.0 {
+        first.1
+    } else if value >= last.0 {
+        last.1
+    } else {
+        let upper = scale.iter().position(|(v, _)| *v >= value)?;
+        let ((from, from_color), (to, to_color)) = (scale[upper - 1], scale[upper]);
+        let factor = (value - from) / (to - from);
+        [0, 1, 2].map(|i| from_color[i] + (to_color[i] - from_color[i]) * factor)
+    };
+    Some(Color::rgb(r, g, b))
+}
+
+#[test]
+fn test_color_on_scale() {
+    let scale = [(20., [0., 1., 0.]), (10., [0., 0., 1.])];
+    assert_eq!(color_on_scale(5., &scale), Some(Color::rgb(0., 0., 1.)));
+    assert_eq!(color_on_scale(15., &scale), Some(Color::rgb(0., 0.5, 0.5)));
+    assert_eq!(color_on_scale(25., &scale), Some(Color::rgb(0., 1., 0.)));
+    assert_eq!(color_on_scale(15., &[]), None);
+}
+
+fn to_color32(color: Color) -> egui::Color32 {
+    let [r, g, b, _] = color.as_rgba_u8();
+    egui::Color32::from_rgb(r, g, b)
+}
+
 pub struct Climate {
     humidity: Option<f64>,
     temperature: Option<f64>,
@@ -33,6 +134,10 @@ pub struct Climate {
     icp: Option<f64>,
     energy: Option<f64>,
     power: Option<f64>,
+    /// Heating or cooling mode of the thermostat, as reported by the backend.
+    mode: Option<String>,
+    /// Valve position in %.
+    valve: Option<f64>,
     last_update: Instant,
     items: HashMap<String, String>,
     config: Option<ClimateConfig>,
@@ -76,6 +181,8 @@ impl Climate {
             icp: None,
             energy: None,
             power: None,
+            mode: None,
+            valve: None,
             azimuth: None,
             wind: None,
             elevation: None,
@@ -85,6 +192,59 @@ impl Climate {
         }
     }
 
+    /// Reading for the key it is received on, e.g. `co2`.
+    fn reading(&self, key: &str) -> Option<f64> {
+        match key {
+            "humidity" => self.humidity,
+            "purity" => self.purity,
+            "co2" => self.co2,
+            "pm10" => self.pm10,
+            "temperature" => self.temperature,
+            "set-temperature" => self.set_temperature,
+            "illuminance" => self.illuminance,
+            "corona" => self.corona,
+            "icp" => self.icp,
+            "power" => self.power,
+            "energy" => self.energy,
+            "wind" => self.wind,
+            _ => None,
+        }
+    }
+
+    /// Tint of the floor according to the overlay, if configured and the reading is available.
+    fn overlay_color(&self) -> Option<Color> {
+        let overlay = self.config.as_ref()?.overlay.as_ref()?;
+        let value = self.reading(&overlay.reading)?;
+        color_on_scale(value as f32, &overlay.scale())
+    }
+
+    /// All readings that are available, as pairs of label and formatted value.
+    fn readings(&self) -> Vec<(&'static str, String)> {
+        let readings = [
+            ("Temperature", self.temperature, "°C"),
+            ("Set temperature", self.set_temperature, "°C"),
+            ("Valve", self.valve, "%"),
+            ("Humidity", self.humidity, "%"),
+            ("CO2", self.co2, "ppm"),
+            ("PM10", self.pm10, "µg/m³"),
+            ("Purity", self.purity, ""),
+            ("Illuminance", self.illuminance, "lx"),
+            ("Wind", self.wind, "km/h"),
+            ("Azimuth", self.azimuth.map(|x| x as f64), "°"),
+            ("Elevation", self.elevation.map(|x| x as f64), "°"),
+            ("Power", self.power, "W"),
+            ("Energy", self.energy, "kWh"),
+            ("Corona", self.corona, ""),
+            ("ICP", self.icp, ""),
+        ];
+        readings
+            .into_iter()
+            .filter_map(|(label, value, unit)| {
+                value.map(|value| (label, format!("{:.1} {}", value, unit).trim().to_string()))
+            })
+            .collect()
+    }
+
     pub fn do_render(&mut self) -> bool {
         if self.last_update.elapsed() > Duration::from_secs(60 * 5) {
             self.last_update = Instant::now();
@@ -93,14 +253,94 @@ impl Climate {
             false
         }
     }
+
+    /// Controls for the thermostat's setpoint, if the widget has an item for it.
+    ///
+    /// The setpoint can be changed in steps or entered directly, it is sent to the item mapped to
+    /// the `set-temperature` key.
+    fn render_thermostat(&self, egui_ui: &mut egui::Ui) -> Vec<WidgetInteraction> {
+        let mut requests = vec![];
+        if !self.items.contains_key(KEY_SET_TEMPERATURE) {
+            return requests;
+        }
+        let step = self
+            .config
+            .as_ref()
+            .and_then(|c| c.setpoint_step)
+            .unwrap_or(DEFAULT_SETPOINT_STEP);
+        let (min, max) = self
+            .config
+            .as_ref()
+            .and_then(|c| c.setpoint_range)
+            .unwrap_or(DEFAULT_SETPOINT_RANGE);
+
+        let current = self.set_temperature;
+        let mut setpoint = current.or(self.temperature).unwrap_or(20.);
+        egui_ui.horizontal(|egui_ui| {
+            egui_ui.label("Thermostat");
+            let minus = egui_ui.button("−").clicked();
+            // Dragging is disabled, otherwise each frame of a drag would send a command
+            let response = egui_ui.add(
+                egui::DragValue::new(&mut setpoint)
+                    .speed(0.)
+                    .max_decimals(1)
+                    .suffix(" °C"),
+            );
+            let plus = egui_ui.button("+").clicked();
+            if minus {
+                setpoint -= step;
+            }
+            if plus {
+                setpoint += step;
+            }
+
+            let setpoint = setpoint.clamp(min, max);
+            let changed = current.map_or(true, |current| (current - setpoint).abs() > 0.01);
+            if changed && (minus || plus || response.lost_focus()) {
+                requests.push(WidgetInteraction::state_change(
+                    KEY_SET_TEMPERATURE,
+                    &format!("{:.1}", setpoint),
+                ));
+            }
+        });
+
+        if let Some(mode) = &self.mode {
+            egui_ui.label(format!("Mode: {}", mode));
+        }
+        if let Some(valve) = self.valve {
+            egui_ui.add(
+                egui::ProgressBar::new(valve as f32 / 100.).text(format!("Valve {:.0} %", valve)),
+            );
+        }
+        egui_ui.separator();
+        requests
+    }
+
+    /// Labels of the thresholds exceeded by the current readings.
+    fn exceeded_thresholds(&self) -> Vec<&str> {
+        let Some(config) = &self.config else {
+            return vec![];
+        };
+        config
+            .thresholds
+            .iter()
+            .filter(|threshold| {
+                self.reading(&threshold.reading).map_or(false, |value| {
+                    threshold.above.map_or(false, |above| value > above)
+                        || threshold.below.map_or(false, |below| value < below)
+                })
+            })
+            .map(|threshold| threshold.label.as_str())
+            .collect()
+    }
 }
 
 impl Item for Climate {
-    fn set_configuration(&mut self, config: &serde_json::Value) {
-        let config: ClimateConfig =
-            serde_json::from_value(config.clone()).expect("Failed to parse config for item");
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        let config: ClimateConfig = serde_json::from_value(config.clone())?;
         info!("Climate: using configuration {:?}", &config);
         self.config = Some(config);
+        Ok(())
     }
 
     fn set_smarthome_items(&mut self, config: &HashMap<String, SmartHomeItemInternal>) {
@@ -118,6 +358,8 @@ impl Item for Climate {
             "pm10" => self.pm10 = self.parse(new_state),
             "temperature" => self.temperature = self.parse(new_state),
             "set-temperature" => self.set_temperature = self.parse(new_state),
+            "mode" => self.mode = Some(new_state.to_string()).filter(|mode| mode != "NULL"),
+            "valve" => self.valve = parse_value(new_state),
             "illuminance" => self.illuminance = self.parse(new_state),
             "corona" => self.corona = self.parse(new_state),
             "icp" => self.icp = self.parse(new_state),
@@ -135,11 +377,86 @@ impl Item for Climate {
         }
     }
 
+    fn get_notification_status(&self) -> Option<NotificationStatus> {
+        let exceeded = self.exceeded_thresholds();
+        let label = exceeded.first()?;
+        Some(NotificationStatus {
+            color: "red".to_string(),
+            priority: 3,
+            num: exceeded.len() as u32,
+            label: label.to_string(),
+        })
+    }
+
+    /// Card with the room's temperature, humidity and CO2, plus the legend of the overlay.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let readings = [
+            (self.temperature, "°C"),
+            (self.humidity, "%"),
+            (self.co2, "ppm"),
+        ]
+        .into_iter()
+        .filter_map(|(value, unit)| value.map(|value| format!("{:.0} {}", value, unit)))
+        .collect::<Vec<_>>();
+        let overlay = self.config.as_ref().and_then(|c| c.overlay.as_ref());
+        if readings.is_empty() && overlay.is_none() {
+            return vec![];
+        }
+
+        ui::render_info_card(context, render_setting, render_position, |egui_ui| {
+            egui_ui.label(RichText::new(readings.join("  ")).strong());
+            if let Some(overlay) = overlay {
+                egui_ui.horizontal(|egui_ui| {
+                    let mut scale = overlay.scale();
+                    scale.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    for (value, _) in &scale {
+                        if let Some(color) = color_on_scale(*value, &scale) {
+                            egui_ui.label(RichText::new("■").color(to_color32(color)));
+                        }
+                        egui_ui.label(RichText::new(value.to_string()).small());
+                    }
+                });
+            }
+        })
+    }
+
+    /// List of all readings of this climate widget.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+            let requests = self.render_thermostat(egui_ui);
+            egui::Grid::new(render_setting.id.with("readings"))
+                .striped(true)
+                .show(egui_ui, |egui_ui| {
+                    for (label, value) in self.readings() {
+                        egui_ui.label(label);
+                        egui_ui.label(value);
+                        egui_ui.end_row();
+                    }
+                });
+            requests
+        })
+    }
+
     fn state_to_blender(
         &self,
-        _blender_item: &EntityName,
+        blender_item: &EntityName,
         modification: SceneModification,
     ) -> Vec<SceneModificationEvent> {
+        if let SceneModification::Highlight() = modification {
+            return vec![SceneModificationEvent::TintModification(TintModification {
+                entity_name: blender_item.to_string(),
+                color: self.overlay_color(),
+            })];
+        }
         if let Some(illuminance) = self.illuminance {
             //     let default_world_mapping = vec![(0., 0.03), (10000., 0.33)];
 
@@ -2,10 +2,15 @@ use crate::events::{LightModification, SceneModificationEvent};
 use crate::openhab::{RequestedStateChangeFromWidget, WidgetInteraction};
 use crate::{emoji, ui};
 use bevy::prelude::*;
-use bevy_egui::egui::{self, Color32};
+use bevy_egui::egui::{
+    self,
+    ecolor::Hsva,
+    widgets::color_picker::{color_picker_hsva_2d, Alpha},
+    Color32,
+};
 
 use crate::config;
-use crate::item::{Item, NotificationStatus};
+use crate::item::{self, Item, NotificationStatus};
 use crate::widget_settings::SceneModification;
 use crate::widget_settings::{EntityName, WidgetRenderSetting};
 
@@ -51,6 +56,7 @@ impl Item for Color {
                 color: "yellow".to_string(),
                 priority: 1,
                 num: 1,
+                label: "lights on".to_string(),
             })
         } else {
             None
@@ -81,6 +87,73 @@ impl Item for Color {
         }
     }
 
+    fn scene_clicked(&self, widget_name: &str) -> Vec<WidgetInteraction> {
+        item::toggle_interaction(widget_name, self.state[2] != 0., None, None)
+    }
+
+    /// Hue, saturation and brightness picker.
+    ///
+    /// Like the sliders of other widgets, the picked color is only sent once the pointer
+    /// dragging the picker is released.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+            let mut requests = vec![];
+            let id = render_setting.id.with("hsb");
+
+            let mut hsva = egui_ui
+                .data(|d| d.get_temp::<Hsva>(id))
+                .unwrap_or(Hsva::new(
+                    self.state[0] / 360.,
+                    self.state[1] / 100.,
+                    self.state[2] / 100.,
+                    1.,
+                ));
+            let picker = egui_ui.scope(|ui| color_picker_hsva_2d(ui, &mut hsva, Alpha::Opaque));
+            if picker.inner {
+                egui_ui.data_mut(|d| d.insert_temp(id, hsva));
+            }
+
+            // Only releasing the pointer that was pressed on the picker sends the color, e.g.
+            // not clicking "Close" after picking.
+            let dragging_id = id.with("dragging");
+            let (pressed_on_picker, released) = egui_ui.input(|i| {
+                let pressed_on_picker = i.pointer.any_pressed()
+                    && i.pointer
+                        .press_origin()
+                        .map_or(false, |pos| picker.response.rect.contains(pos));
+                (pressed_on_picker, i.pointer.any_released())
+            });
+            if pressed_on_picker {
+                egui_ui.data_mut(|d| d.insert_temp(dragging_id, true));
+            }
+            if released {
+                let dragging = egui_ui.data(|d| d.get_temp::<bool>(dragging_id));
+                let picked = egui_ui.data(|d| d.get_temp::<Hsva>(id));
+                if let (Some(true), Some(hsva)) = (dragging, picked) {
+                    requests.push(WidgetInteraction::state_change(
+                        &render_setting.widget_name,
+                        &format!(
+                            "{:.0},{:.0},{:.0}",
+                            hsva.h * 360.,
+                            hsva.s * 100.,
+                            hsva.v * 100.
+                        ),
+                    ));
+                }
+                egui_ui.data_mut(|d| {
+                    d.remove::<Hsva>(id);
+                    d.remove::<bool>(dragging_id);
+                });
+            }
+
+            requests
+        })
+    }
+
     /// Render a Switch
     fn render_egui(
         &self,
@@ -95,7 +168,8 @@ impl Item for Color {
             render_position,
             self.state[2] != 0.,
             None,
-            emoji::get_emoji(emoji::LIGHT_BULB),
+            None,
+            emoji::light_bulb(),
             self.color.unwrap_or(ui::DARK_YELLOW),
             None,
             None,
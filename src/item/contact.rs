@@ -66,6 +66,7 @@ fn get_notification_status(
             color: "red".to_string(),
             priority: 2,
             num: 1,
+            label: "contacts open".to_string(),
         })
     } else {
         None
@@ -5,7 +5,7 @@ use crate::openhab::{RequestedStateChangeFromWidget, WidgetInteraction};
 use crate::{emoji, ui};
 
 use crate::config::SmartHomeItemInternal;
-use crate::item::{Item, NotificationStatus};
+use crate::item::{self, ChangeStatus, Item, NotificationStatus};
 use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 
 macro_rules! log {
@@ -15,11 +15,14 @@ macro_rules! log {
 use std::collections::HashMap;
 
 const KEY_COLORTEMP: &str = "colortemp";
+// Color temperature items of dimmers are percentages, from warm to cold white.
+const COLORTEMP_RANGE: std::ops::RangeInclusive<f32> = 0.0..=100.0;
 
 pub struct Dimmer {
     state: bool,
     value: u8,
-    toggling: bool,
+    /// Status of the last change of the brightness, until the backend reports a new state.
+    change_status: Option<ChangeStatus>,
     color_temperature: Option<f32>,
     has_color_temperature: bool,
 }
@@ -36,7 +39,7 @@ impl Dimmer {
         Dimmer {
             state: false,
             value: 0,
-            toggling: false,
+            change_status: None,
             color_temperature: None,
             has_color_temperature,
         }
@@ -66,7 +69,17 @@ impl Item for Dimmer {
             };
 
             self.state = self.value != 0;
-            self.toggling = false;
+            self.change_status = None;
+        }
+    }
+
+    fn is_optimistic(&self, state_key: &str) -> bool {
+        state_key != KEY_COLORTEMP
+    }
+
+    fn change_status(&mut self, state_key: &str, status: ChangeStatus) {
+        if state_key != KEY_COLORTEMP {
+            self.change_status = Some(status).filter(|s| *s != ChangeStatus::Confirmed);
         }
     }
 
@@ -122,12 +135,58 @@ impl Item for Dimmer {
                 color: "yellow".to_string(),
                 priority: 1,
                 num: 1,
+                label: "lights on".to_string(),
             })
         } else {
             None
         }
     }
 
+    fn scene_clicked(&self, widget_name: &str) -> Vec<WidgetInteraction> {
+        item::toggle_interaction(widget_name, self.state, Some("100"), Some("0"))
+    }
+
+    /// Brightness and, if available, color temperature sliders.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+            let mut requests = vec![];
+
+            if let Some(value) = ui::render_state_slider(
+                egui_ui,
+                render_setting.id.with("brightness"),
+                self.value as f32,
+                0.0..=100.0,
+                "Brightness",
+            ) {
+                requests.push(WidgetInteraction::state_change(
+                    &render_setting.widget_name,
+                    &format!("{:.0}", value),
+                ));
+            }
+
+            if self.has_color_temperature {
+                if let Some(value) = ui::render_state_slider(
+                    egui_ui,
+                    render_setting.id.with(KEY_COLORTEMP),
+                    self.color_temperature.unwrap_or(0.),
+                    COLORTEMP_RANGE,
+                    "Color temperature",
+                ) {
+                    requests.push(WidgetInteraction::state_change(
+                        KEY_COLORTEMP,
+                        &format!("{:.0}", value),
+                    ));
+                }
+            }
+
+            requests
+        })
+    }
+
     /// Render a Switch
     fn render_egui(
         &self,
@@ -141,7 +200,8 @@ impl Item for Dimmer {
             render_position,
             self.state,
             None,
-            emoji::get_emoji(emoji::LIGHT_BULB),
+            self.change_status,
+            emoji::light_bulb(),
             ui::DARK_YELLOW,
             Some("100"),
             Some("0"),
@@ -10,7 +10,10 @@ use crate::openhab::{RequestedStateChangeFromWidget, WidgetInteraction};
 use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 use crate::{emoji, ui};
 
-use super::Item;
+use super::{Item, NotificationStatus};
+
+// Color temperatures of the group are given in Kelvin.
+const COLORTEMP_RANGE: std::ops::RangeInclusive<f32> = 2000.0..=6500.0;
 
 #[derive(Clone, Copy, Debug)]
 enum AggregateType {
@@ -100,6 +103,19 @@ impl Item for DimmerGroup {
         self.update_color();
     }
 
+    fn get_notification_status(&self) -> Option<NotificationStatus> {
+        if self.is_on() {
+            Some(NotificationStatus {
+                color: "yellow".to_string(),
+                priority: 1,
+                num: 1,
+                label: "lights on".to_string(),
+            })
+        } else {
+            None
+        }
+    }
+
     fn state_to_blender(
         &self,
         entity_name: &EntityName,
@@ -121,6 +137,49 @@ impl Item for DimmerGroup {
         }
     }
 
+    fn scene_clicked(&self, widget_name: &str) -> Vec<WidgetInteraction> {
+        super::toggle_interaction(widget_name, self.is_on(), Some("100"), Some("0"))
+    }
+
+    /// Sliders for all dimmers of the group as well as the upper and lower ones separately.
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+            let mut requests = vec![];
+
+            let sliders = [
+                ("all", "All", self.avg_dimm_all * 100., 0.0..=100.0),
+                ("allup", "Up", self.avg_dimm_up * 100., 0.0..=100.0),
+                ("alldown", "Down", self.avg_dimm_down * 100., 0.0..=100.0),
+                (
+                    "allcolortemp",
+                    "Color temperature",
+                    self.avg_color_temp,
+                    COLORTEMP_RANGE,
+                ),
+            ];
+            for (key, text, current, range) in sliders {
+                if let Some(value) = ui::render_state_slider(
+                    egui_ui,
+                    render_setting.id.with(key),
+                    current,
+                    range,
+                    text,
+                ) {
+                    requests.push(WidgetInteraction::state_change(
+                        key,
+                        &format!("{:.0}", value),
+                    ));
+                }
+            }
+
+            requests
+        })
+    }
+
     fn render_egui(
         &self,
         render_position: (f32, f32),
@@ -133,7 +192,8 @@ impl Item for DimmerGroup {
             render_position,
             self.is_on(),
             None,
-            emoji::get_emoji("1F4A1"),
+            None,
+            emoji::light_bulb(),
             self.color,
             Some("100"),
             Some("0"),
@@ -1,24 +1,166 @@
+use std::collections::HashMap;
+
+use bevy::log::error;
+use bevy::math::Vec3;
+use bevy_egui::egui;
+use chrono::{DateTime, Datelike, Local};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::error;
+use crate::config::SmartHomeItemInternal;
+use crate::events::{EnergyFlowModification, SceneModificationEvent};
 use crate::item::generic_item::{generic_translate_value, GenericItem};
+use crate::item::Item;
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 
 // Number of highest power meter values to take for display.
 const NUM_HIGHEST: usize = 3;
-const DEFAULT_VOLTAGE: usize = 230;
+const DEFAULT_PHASES: usize = 3;
+const DEFAULT_VOLTAGE: f32 = 230.;
+const DEFAULT_POWER_FACTOR: f32 = 1.;
+const MISC_LABEL: &str = "Misc";
+const TOTAL_LABEL: &str = "Total";
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default)]
 pub struct PowerMeter {
     pub name: String,
     pub label: String,
     pub unit: Option<String>,
+    /// Path in the 3D scene along which the energy flow of this meter is shown. Either the name
+    /// of a polyline in `paths` or of a mesh in the scene.
+    pub path: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct EnergyMonitorConfig {
     pub power_meters: Vec<PowerMeter>,
+    /// Polylines for energy flows, given as list of points in the 3D scene.
+    #[serde(default)]
+    pub paths: HashMap<String, Vec<[f32; 3]>>,
+    /// Number of phases with a current meter, keys `l1_strom` to `lN_strom`. Defaults to 3.
+    pub phases: Option<usize>,
+    /// Voltage of each phase in V. Defaults to 230 V.
+    pub voltage: Option<f32>,
+    /// Power factor to calculate real power from voltage and current. Defaults to 1.
+    pub power_factor: Option<f32>,
+}
+
+impl EnergyMonitorConfig {
+    fn phases(&self) -> usize {
+        self.phases.unwrap_or(DEFAULT_PHASES)
+    }
+
+    /// Real power in W for the given current in A.
+    fn current_to_watts(&self, current: f32) -> f32 {
+        current
+            * self.voltage.unwrap_or(DEFAULT_VOLTAGE)
+            * self.power_factor.unwrap_or(DEFAULT_POWER_FACTOR)
+    }
+}
+
+/// Energy consumed by a meter, integrated over time from its power.
+///
+/// Counters are stored across restarts. The power is not, as it's unknown while the app isn't
+/// running.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct EnergyCounter {
+    /// Power in W since the last update.
+    #[serde(skip)]
+    power: f32,
+    last_update: Option<DateTime<Local>>,
+    /// Consumption in kWh of the current day, week and month.
+    pub(crate) day: f64,
+    pub(crate) week: f64,
+    pub(crate) month: f64,
+}
+
+impl EnergyCounter {
+    /// Add the energy consumed since the last update and continue with the given power.
+    ///
+    /// The power is assumed to be constant between updates. Only consumption is counted,
+    /// negative power (e.g. feed-in) is ignored. Energy of an interval spanning the start of a
+    /// new day, week or month is counted for the new one.
+    pub(crate) fn update(&mut self, power: f32, now: DateTime<Local>) {
+        if let Some(last_update) = self.last_update {
+            if now.date_naive() != last_update.date_naive() {
+                self.day = 0.;
+            }
+            if now.iso_week() != last_update.iso_week() {
+                self.week = 0.;
+            }
+            if (now.year(), now.month()) != (last_update.year(), last_update.month()) {
+                self.month = 0.;
+            }
+
+            let hours = (now - last_update).num_milliseconds().max(0) as f64 / 3_600_000.;
+            let energy = self.power.max(0.) as f64 * hours / 1000.;
+            self.day += energy;
+            self.week += energy;
+            self.month += energy;
+        }
+        self.power = power;
+        self.last_update = Some(now);
+    }
+}
+
+#[test]
+fn test_energy_counter() {
+    use chrono::TimeZone;
+
+    let at = |day, hour| Local.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap();
+    let mut counter = EnergyCounter::default();
+    counter.update(1000., at(1, 10));
+    assert_eq!(counter.day, 0.);
+
+    // 1 kW for two hours
+    counter.update(-500., at(1, 12));
+    assert_eq!((counter.day, counter.week, counter.month), (2., 2., 2.));
+
+    // Feed-in is not counted
+    counter.update(2000., at(1, 13));
+    assert_eq!(counter.day, 2.);
+
+    // Next day, same week and month: 2 kW for 12 hours
+    counter.update(0., at(2, 1));
+    assert_eq!((counter.day, counter.week, counter.month), (24., 26., 26.));
+
+    // Next week, 2024-05-06 is a Monday
+    counter.update(0., at(6, 1));
+    assert_eq!((counter.day, counter.week, counter.month), (0., 0., 26.));
+}
+
+#[test]
+fn test_stored_energy_counter() {
+    use chrono::TimeZone;
+
+    let at = |hour| Local.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+    let mut counter = EnergyCounter::default();
+    counter.update(1000., at(10));
+    counter.update(1000., at(11));
+
+    // The time the app wasn't running is not counted
+    let stored = serde_json::to_value(&counter).unwrap();
+    let mut counter: EnergyCounter = serde_json::from_value(stored).unwrap();
+    counter.update(1000., at(15));
+    assert_eq!(counter.day, 1.);
+    counter.update(0., at(16));
+    assert_eq!(counter.day, 2.);
+}
+
+/// Current power in W of the phases and meters of an energy monitor.
+struct PowerReadings {
+    /// Power per phase, if known.
+    phases: Vec<Option<f32>>,
+    /// Label and power of each configured power meter with a known state.
+    meters: Vec<(String, f32)>,
+    /// Power of all phases together.
+    total: f32,
+    /// Power not covered by any of the power meters.
+    misc: f32,
 }
 
 fn translate_value(key: &str, input: &str) -> Value {
@@ -33,11 +175,23 @@ fn translate_value(key: &str, input: &str) -> Value {
     }
 }
 
-fn parse_power_meter_to_watts(input: &str, unit: Option<&str>) -> f32 {
+fn parse_power_meter_to_watts(
+    input: &str,
+    unit: Option<&str>,
+    config: &EnergyMonitorConfig,
+) -> f32 {
     let tokens: Vec<&str> = input.split(" ").collect();
-    let value = tokens.get(0).map(|s| s.parse::<f32>());
+    let value = match tokens.get(0).map(|s| s.parse::<f32>()) {
+        Some(Ok(value)) => value,
+        _ => {
+            if input != "NULL" {
+                error!("Failed to parse power meter value: {}", input);
+            }
+            return 0.0;
+        }
+    };
     match tokens.get(1) {
-        Some(&"W") => value.unwrap().unwrap(),
+        Some(&"W") => value,
         Some(unknown_unit) => {
             error!(
                 "Failed to parse unknown unit for power meter: {}",
@@ -46,36 +200,57 @@ fn parse_power_meter_to_watts(input: &str, unit: Option<&str>) -> f32 {
             0.0
         }
         None => match unit {
-            Some("mA") | Some("miliampere") => {
-                value.unwrap().unwrap() * DEFAULT_VOLTAGE as f32 / 1000.
-            }
-            Some(_) | None => value.unwrap().unwrap(),
+            Some("mA") | Some("miliampere") => config.current_to_watts(value / 1000.),
+            Some(_) | None => value,
         },
     }
 }
 
+fn power_readings(generic_item: &GenericItem<EnergyMonitorConfig>) -> PowerReadings {
+    let default_config = EnergyMonitorConfig::default();
+    let config = generic_item.config.as_ref().unwrap_or(&default_config);
+
+    let phases: Vec<Option<f32>> = (1..=config.phases())
+        .map(|i| {
+            let current = generic_item.state.get(&format!("l{}_strom", i))?;
+            let current = current.0.parse::<f32>().ok()?;
+            Some(config.current_to_watts(current))
+        })
+        .collect();
+    let total = phases.iter().flatten().sum();
+
+    let meters: Vec<(String, f32)> = config
+        .power_meters
+        .iter()
+        .filter_map(|power_meter| {
+            let state = generic_item.state.get(&power_meter.name)?;
+            let power = parse_power_meter_to_watts(&state.0, power_meter.unit.as_deref(), config);
+            Some((power_meter.label.to_string(), power))
+        })
+        .collect();
+    let meters_total: f32 = meters.iter().map(|(_, power)| power.max(0.)).sum();
+
+    PowerReadings {
+        phases,
+        meters,
+        total,
+        misc: total - meters_total,
+    }
+}
+
 fn init_map(generic_item: &GenericItem<EnergyMonitorConfig>) -> serde_json::Map<String, Value> {
     let mut map = serde_json::Map::new();
+    let readings = power_readings(generic_item);
 
-    // XXX That's not the energy, it's the power!
-    let mut energy_total = 0.0;
-    for i in 1..4 {
-        let parsed = generic_item
-            .state
-            .get(&format!("l{}_strom", i))
-            .map(|s| s.0.parse::<f32>());
-        let (energy_as_str, energy) = match parsed {
-            None => ("n.a.".to_string(), 0.0),
-            Some(s) => match s {
-                Ok(s) => (format!("{:.1}", s * 230.0), s * 230.0),
-                Err(_) => ("n.a.".to_string(), 0.0),
-            },
-        };
-        map.insert(
-            format!("l{}_energy_calculated", i),
-            Value::String(energy_as_str),
+    for (i, power) in readings.phases.iter().enumerate() {
+        let power = Value::String(
+            power
+                .map(|power| format!("{:.1}", power))
+                .unwrap_or("n.a.".to_string()),
         );
-        energy_total += energy;
+        map.insert(format!("l{}_power", i + 1), power.clone());
+        // Previous name, still used by existing templates
+        map.insert(format!("l{}_energy_calculated", i + 1), power);
     }
 
     // Power value for configured power meters
@@ -83,34 +258,24 @@ fn init_map(generic_item: &GenericItem<EnergyMonitorConfig>) -> serde_json::Map<
     // Need this for sorting
     let mut power_meter_values = vec![];
     let mut power_meter_totals = 0.0;
-    if let Some(config) = &generic_item.config {
-        for power_meter in &config.power_meters {
-            let state = generic_item.state.get(&power_meter.name);
-            if let Some(state) = state {
-                let parsed = parse_power_meter_to_watts(
-                    &state.0,
-                    power_meter.unit.as_ref().map(|s| s.as_str()),
-                );
-                if parsed > 0. {
-                    let formatted = format!("{:.0} W", parsed);
-                    power_meters.push((power_meter.label.to_string(), formatted.clone()));
-                    power_meter_values.push((power_meter.label.to_string(), parsed, formatted));
-                    power_meter_totals += parsed;
-                }
-            }
+    for (label, power) in &readings.meters {
+        if *power > 0. {
+            let formatted = format!("{:.0} W", power);
+            power_meters.push((label.to_string(), formatted.clone()));
+            power_meter_values.push((label.to_string(), *power, formatted));
+            power_meter_totals += power;
         }
     }
 
-    // Calculate the remainder of the power consumption and add it to the list
-    let misc_power = energy_total - power_meter_totals;
+    // Remainder of the power consumption
     map.insert(
         "misc_power".to_string(),
-        Value::String(format!("{:.1} W", misc_power)),
+        Value::String(format!("{:.1} W", readings.misc)),
     );
     power_meter_values.push((
-        "Misc".to_string(),
-        misc_power,
-        format!("{:.1} W", misc_power),
+        MISC_LABEL.to_string(),
+        readings.misc,
+        format!("{:.1} W", readings.misc),
     ));
 
     // Sort power meter values and take the NUM_HIGHEST highest ones.
@@ -135,10 +300,10 @@ fn init_map(generic_item: &GenericItem<EnergyMonitorConfig>) -> serde_json::Map<
         serde_json::to_value(power_meter_highest).unwrap(),
     );
 
-    map.insert(
-        "energy_total".to_string(),
-        Value::String(format!("{:.1}", energy_total)),
-    );
+    let power_total = Value::String(format!("{:.1}", readings.total));
+    map.insert("power_total".to_string(), power_total.clone());
+    // Previous name, still used by existing templates
+    map.insert("energy_total".to_string(), power_total);
     map
 }
 
@@ -151,6 +316,252 @@ fn render_slider(generic_item: &GenericItem<EnergyMonitorConfig>) -> Option<(Str
     }
 }
 
-pub fn new() -> GenericItem<EnergyMonitorConfig> {
-    GenericItem::with_custom_functions(translate_value, init_map, render_slider)
+/// Show the power of the meters configured for the given path as energy flow.
+///
+/// Negative values (e.g. PV export) reverse the direction of the flow.
+fn state_to_blender(
+    generic_item: &GenericItem<EnergyMonitorConfig>,
+    entity_name: &EntityName,
+    modification: SceneModification,
+) -> Vec<SceneModificationEvent> {
+    let Some(config) = &generic_item.config else {
+        return vec![];
+    };
+    match modification {
+        SceneModification::Flow() => {
+            let power = config
+                .power_meters
+                .iter()
+                .filter(|power_meter| power_meter.path.as_ref() == Some(entity_name))
+                .filter_map(|power_meter| {
+                    generic_item.state.get(&power_meter.name).map(|state| {
+                        parse_power_meter_to_watts(&state.0, power_meter.unit.as_deref(), config)
+                    })
+                })
+                .sum();
+            let points = config
+                .paths
+                .get(entity_name)
+                .map(|points| points.iter().map(|p| Vec3::from(*p)).collect());
+
+            vec![SceneModificationEvent::EnergyFlowModification(
+                EnergyFlowModification {
+                    path_name: entity_name.to_string(),
+                    points,
+                    power,
+                },
+            )]
+        }
+        _ => {
+            error!(
+                "Unsupported modification {:?} in EnergyMonitor",
+                modification
+            );
+            vec![]
+        }
+    }
+}
+
+/// Energy monitor showing the power of all phases and power meters as well as the energy they
+/// consumed.
+///
+/// Energy is integrated from the power reported by the backend, so consumption is only counted
+/// while the app is running. The counters are kept across restarts.
+pub struct EnergyMonitor {
+    generic_item: GenericItem<EnergyMonitorConfig>,
+    /// Energy consumed per power meter label, including the remainder and the total.
+    energy: HashMap<String, EnergyCounter>,
+}
+
+impl EnergyMonitor {
+    /// Update the energy counters with the current power readings.
+    fn update_energy(&mut self) {
+        let now = Local::now();
+        let readings = power_readings(&self.generic_item);
+        let powers = readings.meters.into_iter().chain([
+            (MISC_LABEL.to_string(), readings.misc),
+            (TOTAL_LABEL.to_string(), readings.total),
+        ]);
+        for (label, power) in powers {
+            self.energy.entry(label).or_default().update(power, now);
+        }
+    }
+
+    /// Consumption in kWh per power meter, in the order of the configuration.
+    fn consumption(&self) -> Vec<(&str, &EnergyCounter)> {
+        let labels = self
+            .generic_item
+            .config
+            .iter()
+            .flat_map(|config| config.power_meters.iter().map(|p| p.label.as_str()))
+            .chain([MISC_LABEL, TOTAL_LABEL]);
+        labels
+            .filter_map(|label| self.energy.get(label).map(|counter| (label, counter)))
+            .collect()
+    }
+}
+
+impl Item for EnergyMonitor {
+    fn state_changed(&mut self, state_key: &str, new_state: &str) {
+        self.generic_item.state_changed(state_key, new_state);
+        self.update_energy();
+    }
+
+    fn initial_state(&mut self, state_key: &str, new_state: &str) {
+        self.generic_item.initial_state(state_key, new_state);
+        self.update_energy();
+    }
+
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        self.generic_item.set_configuration(config)
+    }
+
+    fn set_smarthome_items(&mut self, items: &HashMap<String, SmartHomeItemInternal>) {
+        self.generic_item.set_smarthome_items(items);
+    }
+
+    fn render_slider(&self) -> Option<(String, usize)> {
+        self.generic_item.render_slider()
+    }
+
+    /// State of the generic item plus the consumption of the current day, week and month as list
+    /// of `[label, day, week, month]`.
+    fn state_as_json(&self) -> serde_json::Map<String, Value> {
+        let mut map = self.generic_item.state_as_json();
+        let consumption: Vec<[String; 4]> = self
+            .consumption()
+            .into_iter()
+            .map(|(label, counter)| {
+                [
+                    label.to_string(),
+                    format!("{:.2} kWh", counter.day),
+                    format!("{:.2} kWh", counter.week),
+                    format!("{:.2} kWh", counter.month),
+                ]
+            })
+            .collect();
+        map.insert(
+            "consumption".to_string(),
+            serde_json::to_value(consumption).unwrap(),
+        );
+        map
+    }
+
+    fn state_to_blender(
+        &self,
+        entity_name: &EntityName,
+        modification: SceneModification,
+    ) -> Vec<SceneModificationEvent> {
+        self.generic_item
+            .state_to_blender(entity_name, modification)
+    }
+
+    /// Card with the total power and today's consumption. Opens the report on right click.
+    fn render_egui(
+        &self,
+        render_position: (f32, f32),
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let readings = power_readings(&self.generic_item);
+        let today = self.energy.get(TOTAL_LABEL).map(|counter| counter.day);
+        ui::render_info_card(context, render_setting, render_position, |egui_ui| {
+            egui_ui.strong(format!("⚡ {:.0} W", readings.total));
+            if let Some(today) = today {
+                egui_ui.label(format!("{:.2} kWh today", today));
+            }
+        })
+    }
+
+    /// Clicking the energy monitor in the scene opens the report.
+    fn scene_clicked(&self, _widget_name: &str) -> Vec<WidgetInteraction> {
+        vec![WidgetInteraction::FullscreenRequest(true)]
+    }
+
+    fn stored_state(&self) -> Option<Value> {
+        serde_json::to_value(&self.energy).ok()
+    }
+
+    fn restore_state(&mut self, state: &Value) {
+        match serde_json::from_value::<HashMap<String, EnergyCounter>>(state.clone()) {
+            Ok(energy) => self.energy = energy,
+            Err(e) => error!("Failed to restore energy counters: {:?}", e),
+        }
+    }
+
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        ctx: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let readings = power_readings(&self.generic_item);
+        let consumption = self.consumption();
+        ui::render_fullscreen_window(ctx, render_setting, |ui| {
+            egui::Grid::new(render_setting.id.with("power"))
+                .striped(true)
+                .show(ui, |ui| {
+                    for (i, power) in readings.phases.iter().enumerate() {
+                        ui.label(format!("Phase {}", i + 1));
+                        ui.label(match power {
+                            Some(power) => format!("{:.0} W", power),
+                            None => "n.a.".to_string(),
+                        });
+                        ui.end_row();
+                    }
+                    for (label, power) in &readings.meters {
+                        ui.label(label);
+                        ui.label(format!("{:.0} W", power));
+                        ui.end_row();
+                    }
+                    ui.label(MISC_LABEL);
+                    ui.label(format!("{:.0} W", readings.misc));
+                    ui.end_row();
+                    ui.strong(TOTAL_LABEL);
+                    ui.strong(format!("{:.0} W", readings.total));
+                    ui.end_row();
+                });
+            ui.separator();
+            egui::Grid::new(render_setting.id.with("consumption"))
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("kWh");
+                    ui.strong("Today");
+                    ui.strong("This week");
+                    ui.strong("This month");
+                    ui.end_row();
+                    for (label, counter) in &consumption {
+                        ui.label(*label);
+                        ui.label(format!("{:.2}", counter.day));
+                        ui.label(format!("{:.2}", counter.week));
+                        ui.label(format!("{:.2}", counter.month));
+                        ui.end_row();
+                    }
+                });
+            vec![]
+        })
+    }
+}
+
+pub fn new() -> EnergyMonitor {
+    let mut generic_item =
+        GenericItem::with_custom_functions(translate_value, init_map, render_slider);
+    generic_item.with_blender_f(state_to_blender);
+    EnergyMonitor {
+        generic_item,
+        energy: HashMap::new(),
+    }
+}
+
+#[test]
+fn test_template_keys() {
+    let mut monitor = new();
+    monitor.state_changed("l1_strom", "2");
+    let map = monitor.state_as_json();
+    // Templates may still use the previous names
+    assert_eq!(map["l1_power"], map["l1_energy_calculated"]);
+    assert_eq!(
+        map["l2_energy_calculated"],
+        Value::String("n.a.".to_string())
+    );
+    assert_eq!(map["power_total"], map["energy_total"]);
 }
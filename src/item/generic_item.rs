@@ -2,6 +2,7 @@ use crate::config::SmartHomeItemInternal;
 use crate::events::SceneModificationEvent;
 use crate::item::Item;
 use crate::openhab::{RequestedStateChangeFromWidget, WidgetInteraction};
+use crate::ui;
 use crate::widget_settings::EntityName;
 use crate::widget_settings::{SceneModification, WidgetRenderSetting};
 
@@ -32,8 +33,20 @@ pub struct GenericItem<T: DeserializeOwned> {
     translate_f: fn(&str, &str) -> Value,
     init_f: fn(&GenericItem<T>) -> serde_json::Map<String, Value>,
     render_slider_f: fn(&GenericItem<T>) -> Option<(String, usize)>,
-    blender_f: Option<fn(&GenericItem<T>, &str, &str, f32) -> Vec<SceneModificationEvent>>,
+    blender_f:
+        Option<fn(&GenericItem<T>, &EntityName, SceneModification) -> Vec<SceneModificationEvent>>,
     notification_f: Option<fn(&GenericItem<T>) -> Option<NotificationStatus>>,
+    fullscreen_f: Option<
+        fn(&GenericItem<T>, &WidgetRenderSetting, &mut egui::Context) -> Vec<WidgetInteraction>,
+    >,
+    render_egui_f: Option<
+        fn(
+            &GenericItem<T>,
+            (f32, f32),
+            &WidgetRenderSetting,
+            &mut egui::Context,
+        ) -> Vec<WidgetInteraction>,
+    >,
 }
 
 impl<T: DeserializeOwned> Default for GenericItem<T> {
@@ -47,7 +60,8 @@ impl<T: DeserializeOwned> Default for GenericItem<T> {
             render_slider_f: generic_render_slider,
             blender_f: None,
             notification_f: None,
-            ..Default::default()
+            fullscreen_f: None,
+            render_egui_f: None,
         }
     }
 }
@@ -76,12 +90,33 @@ impl<T: DeserializeOwned> GenericItem<T> {
 
     pub fn with_blender_f<'a>(
         &'a mut self,
-        f: fn(&GenericItem<T>, &str, &str, f32) -> Vec<SceneModificationEvent>,
+        f: fn(&GenericItem<T>, &EntityName, SceneModification) -> Vec<SceneModificationEvent>,
     ) -> &'a mut Self {
         self.blender_f = Some(f);
         self
     }
 
+    pub fn with_fullscreen_f<'a>(
+        &'a mut self,
+        f: fn(&GenericItem<T>, &WidgetRenderSetting, &mut egui::Context) -> Vec<WidgetInteraction>,
+    ) -> &'a mut Self {
+        self.fullscreen_f = Some(f);
+        self
+    }
+
+    pub fn with_render_egui_f<'a>(
+        &'a mut self,
+        f: fn(
+            &GenericItem<T>,
+            (f32, f32),
+            &WidgetRenderSetting,
+            &mut egui::Context,
+        ) -> Vec<WidgetInteraction>,
+    ) -> &'a mut Self {
+        self.render_egui_f = Some(f);
+        self
+    }
+
     pub fn with_init_f<'a>(
         &'a mut self,
         f: fn(&GenericItem<T>) -> serde_json::Map<String, Value>,
@@ -92,11 +127,9 @@ impl<T: DeserializeOwned> GenericItem<T> {
 }
 
 impl<T: DeserializeOwned> Item for GenericItem<T> {
-    fn set_configuration(&mut self, config: &serde_json::Value) {
-        self.config = Some(
-            serde_json::from_value(config.clone())
-                .expect("Failed to parse config for generic item."),
-        );
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        self.config = Some(serde_json::from_value(config.clone())?);
+        Ok(())
     }
 
     fn set_smarthome_items(&mut self, items: &HashMap<String, SmartHomeItemInternal>) {
@@ -131,17 +164,68 @@ impl<T: DeserializeOwned> Item for GenericItem<T> {
         (self.render_slider_f)(&self)
     }
 
+    /// Translated state of all keys, the values calculated by the init function and the time
+    /// of the last state change.
+    fn state_as_json(&self) -> serde_json::Map<String, Value> {
+        let mut map = (self.init_f)(self);
+        for (key, (value, _)) in &self.state {
+            map.insert(key.to_string(), (self.translate_f)(key, value));
+        }
+
+        let last_updated = self.state.values().filter_map(|(_, time)| *time).max();
+        map.insert(
+            "__has_last_updated".to_string(),
+            Value::Bool(last_updated.is_some()),
+        );
+        if let Some(last_updated) = last_updated {
+            if let Ok(elapsed) = chrono::Duration::from_std(last_updated.elapsed()) {
+                map.insert(
+                    "__last_updated".to_string(),
+                    Value::String(
+                        HumanTime::from(-elapsed).to_text_en(Accuracy::Rough, Tense::Past),
+                    ),
+                );
+            }
+        }
+        map
+    }
+
     fn render_egui(
         &self,
         render_position: (f32, f32),
         render_setting: &WidgetRenderSetting,
         context: &mut egui::Context,
     ) -> Vec<WidgetInteraction> {
-        vec![]
+        match self.render_egui_f {
+            Some(f) => f(self, render_position, render_setting, context),
+            None => vec![],
+        }
     }
 
     fn get_notification_status(&self) -> Option<super::NotificationStatus> {
-        None
+        self.notification_f.and_then(|f| f(self))
+    }
+
+    fn state_to_blender(
+        &self,
+        entity_name: &EntityName,
+        modification: SceneModification,
+    ) -> Vec<SceneModificationEvent> {
+        match self.blender_f {
+            Some(f) => f(self, entity_name, modification),
+            None => vec![],
+        }
+    }
+
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        context: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        match self.fullscreen_f {
+            Some(f) => f(self, render_setting, context),
+            None => ui::render_fullscreen_window(context, render_setting, |_| vec![]),
+        }
     }
 }
 
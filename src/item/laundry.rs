@@ -1,12 +1,336 @@
+use std::collections::HashMap;
+
+use bevy::log::error;
+use bevy_egui::egui;
+use chrono::{Local, TimeZone};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::item::generic_item::{generic_translate_value, GenericItem};
+use crate::config::SmartHomeItemInternal;
+use crate::history::{self, parse_value};
+use crate::item::generic_item::{generic_translate_value, GenericItem, INTERNAL_STATE_PREFIX};
+use crate::item::{Item, NotificationStatus};
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::WidgetRenderSetting;
+
+const KEY_POWER: &str = "power";
+const KEY_ACTIVE: &str = "active";
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+const DEFAULT_THRESHOLD: f32 = 5.;
+const DEFAULT_FINISH_MINUTES: f32 = 3.;
+// Number of completed runs used to estimate the remaining time.
+const MAX_LEARNED_RUNS: usize = 5;
+
+#[derive(Deserialize, JsonSchema, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LaundryConfig {
     pub image: String,
+    /// Power in W below which the appliance is considered idle. Defaults to 5 W.
+    pub threshold: Option<f32>,
+    /// Minutes the power has to stay below the threshold for the program to be finished.
+    /// Defaults to 3 minutes.
+    pub finish_minutes: Option<f32>,
+    /// Minimum power in W and label of the phases of a program, e.g. `[1000, "heating"]`.
+    #[serde(default)]
+    pub phases: Vec<(f32, String)>,
+}
+
+impl LaundryConfig {
+    fn threshold(&self) -> f64 {
+        self.threshold.unwrap_or(DEFAULT_THRESHOLD) as f64
+    }
+
+    fn finish_seconds(&self) -> f64 {
+        self.finish_minutes.unwrap_or(DEFAULT_FINISH_MINUTES) as f64 * 60.
+    }
+}
+
+/// Energy used since the start of a run, as list of seconds since start and energy in Wh.
+fn cumulative_energy(samples: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut energy = 0.;
+    let mut curve = vec![];
+    for (i, [time, _]) in samples.iter().enumerate() {
+        if i > 0 {
+            let [previous_time, previous_power] = samples[i - 1];
+            energy += previous_power * (time - previous_time) / 3600.;
+        }
+        curve.push([*time, energy]);
+    }
+    curve
+}
+
+/// A program run of the appliance.
+#[derive(Clone, Debug)]
+struct Run {
+    start: f64,
+    /// Power in W by seconds since the start.
+    samples: Vec<[f64; 2]>,
+    /// Time since the power is below the threshold.
+    below_since: Option<f64>,
+}
+
+impl Run {
+    /// Energy used so far in Wh, assuming the last power is still drawn.
+    fn energy(&self, now: f64) -> f64 {
+        let mut samples = self.samples.clone();
+        samples.push([now - self.start, 0.]);
+        cumulative_energy(&samples).last().map_or(0., |p| p[1])
+    }
+
+    /// Whether the power stayed below the threshold long enough.
+    fn is_finished(&self, now: f64, config: &LaundryConfig) -> bool {
+        self.below_since
+            .map_or(false, |since| now - since >= config.finish_seconds())
+    }
+}
+
+/// Runs of an appliance, learned from its power.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PowerCurve {
+    run: Option<Run>,
+    /// Cumulative energy curves of completed runs, the most recent last.
+    learned: Vec<Vec<[f64; 2]>>,
+    /// End of the last run, unless dismissed.
+    finished_at: Option<f64>,
+    /// End of the most recent learned run.
+    learned_until: f64,
+}
+
+/// Learned runs as kept across restarts.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredRuns {
+    learned: Vec<Vec<[f64; 2]>>,
+    learned_until: f64,
+}
+
+impl PowerCurve {
+    pub(crate) fn power_changed(&mut self, power: f64, now: f64, config: &LaundryConfig) {
+        self.finish_if_done(now, config);
+
+        let is_on = power >= config.threshold();
+        match &mut self.run {
+            None if is_on => {
+                self.run = Some(Run {
+                    start: now,
+                    samples: vec![[0., power]],
+                    below_since: None,
+                });
+                self.finished_at = None;
+            }
+            None => {}
+            Some(run) => {
+                run.samples.push([now - run.start, power]);
+                run.below_since = match (is_on, run.below_since) {
+                    (true, _) => None,
+                    (false, since) => since.or(Some(now)),
+                };
+            }
+        }
+    }
+
+    /// Complete the current run if the power stayed below the threshold long enough.
+    fn finish_if_done(&mut self, now: f64, config: &LaundryConfig) {
+        let run = match self.run.take() {
+            Some(run) if run.is_finished(now, config) => run,
+            run => {
+                self.run = run;
+                return;
+            }
+        };
+        let end = run.below_since.unwrap_or(now);
+        let samples = run
+            .samples
+            .iter()
+            .copied()
+            .filter(|[time, _]| *time <= end - run.start)
+            .collect::<Vec<_>>();
+        if end > self.learned_until {
+            self.learned.push(cumulative_energy(&samples));
+            if self.learned.len() > MAX_LEARNED_RUNS {
+                self.learned.remove(0);
+            }
+            self.learned_until = end;
+        }
+        self.finished_at = Some(end);
+    }
+
+    /// Learn the runs in past power values, given as timestamp and power, oldest first.
+    ///
+    /// Runs that didn't end after the last learned one are already known. If the values end
+    /// during a run that started before the current one and the appliance is still on, e.g.
+    /// because it was already running when the app started, it becomes the current run.
+    pub(crate) fn learn_from_history(
+        &mut self,
+        datapoints: &[[f64; 2]],
+        power: Option<f64>,
+        now: f64,
+        config: &LaundryConfig,
+    ) {
+        let mut past = PowerCurve {
+            learned: std::mem::take(&mut self.learned),
+            learned_until: self.learned_until,
+            ..Default::default()
+        };
+        for [time, value] in datapoints {
+            past.power_changed(*value, *time, config);
+        }
+        past.finish_if_done(now, config);
+        self.learned = past.learned;
+        self.learned_until = past.learned_until;
+
+        let Some(past_run) = past.run else {
+            return;
+        };
+        let is_off = power.map_or(false, |power| power < config.threshold());
+        if is_off
+            || self
+                .run
+                .as_ref()
+                .map_or(false, |run| run.start <= past_run.start)
+        {
+            return;
+        }
+        // Continue with the values received live after the past ones
+        let last_time = datapoints.last().map_or(now, |p| p[0]);
+        if let Some(current) = self.run.replace(past_run) {
+            for [time, power] in current.samples {
+                if current.start + time > last_time {
+                    self.power_changed(power, current.start + time, config);
+                }
+            }
+        }
+    }
+
+    fn is_running(&self, now: f64, config: &LaundryConfig) -> bool {
+        self.run
+            .as_ref()
+            .map_or(false, |run| !run.is_finished(now, config))
+    }
+
+    fn is_finished(&self, now: f64, config: &LaundryConfig) -> bool {
+        self.finished_at.is_some()
+            || self
+                .run
+                .as_ref()
+                .map_or(false, |run| run.is_finished(now, config))
+    }
+
+    /// Seconds until the current run finishes, averaged over the learned runs.
+    ///
+    /// The progress of the current run is matched to the learned runs by the energy used so far,
+    /// so pauses and slower phases are taken into account.
+    pub(crate) fn remaining(&self, now: f64) -> Option<f64> {
+        let run = self.run.as_ref()?;
+        let energy = run.energy(now);
+        let remaining = self
+            .learned
+            .iter()
+            .filter_map(|curve| {
+                let duration = curve.last()?[0];
+                let elapsed = curve
+                    .iter()
+                    .find(|[_, e]| *e >= energy)
+                    .map_or(duration, |p| p[0]);
+                Some((duration - elapsed).max(0.))
+            })
+            .collect::<Vec<_>>();
+        if remaining.is_empty() {
+            return None;
+        }
+        Some(remaining.iter().sum::<f64>() / remaining.len() as f64)
+    }
+}
+
+#[test]
+fn test_power_curve() {
+    let config = LaundryConfig::default();
+    let mut curve = PowerCurve::default();
+
+    // First run: 2 kW for 30 minutes, then 100 W for 30 minutes
+    curve.power_changed(2000., 0., &config);
+    curve.power_changed(100., 1800., &config);
+    curve.power_changed(0., 3600., &config);
+    assert!(curve.is_running(3700., &config));
+    assert_eq!(curve.remaining(3700.), None);
+
+    // Finished once the power stayed low for 3 minutes
+    assert!(curve.is_finished(3600. + 180., &config));
+    curve.power_changed(2000., 10000., &config);
+    assert_eq!(curve.learned.len(), 1);
+    assert_eq!(curve.learned[0].last(), Some(&[3600., 1050.]));
+    assert_eq!(curve.finished_at, None);
+
+    // Second run used 1000 Wh after 30 minutes, like the first one
+    curve.power_changed(100., 11800., &config);
+    assert_eq!(curve.remaining(11800.), Some(1800.));
+}
+
+#[test]
+fn test_stored_runs() {
+    let config = LaundryConfig::default();
+    let mut laundry = new();
+    laundry.power_curve.power_changed(2000., 0., &config);
+    laundry.power_curve.power_changed(0., 1800., &config);
+    laundry.power_curve.power_changed(2000., 5000., &config);
+
+    // A new instance, e.g. after restarting, estimates right away
+    let mut restored = new();
+    restored.restore_state(&laundry.stored_state().unwrap());
+    restored.power_curve.power_changed(2000., 10000., &config);
+    assert_eq!(restored.power_curve.remaining(10000.), Some(1800.));
+}
+
+#[test]
+fn test_learn_from_history() {
+    let config = LaundryConfig::default();
+    let mut curve = PowerCurve::default();
+    let datapoints = [[0., 2000.], [900., 2000.], [1800., 0.], [5000., 2000.]];
+
+    // The last run is still going on, the appliance was on before the first live value
+    curve.power_changed(2000., 5100., &config);
+    curve.learn_from_history(&datapoints, Some(2000.), 5200., &config);
+    assert_eq!(curve.learned.len(), 1);
+    assert_eq!(curve.run.as_ref().map(|run| run.start), Some(5000.));
+    assert_eq!(curve.remaining(5200.), Some(900.));
+
+    // Runs are only learned once
+    curve.learn_from_history(&datapoints, Some(2000.), 5300., &config);
+    assert_eq!(curve.learned.len(), 1);
+}
+
+#[test]
+fn test_initial_power() {
+    let mut laundry = new();
+    laundry.initial_state(KEY_POWER, "2000");
+    assert_eq!(laundry.power(), Some(2000.));
+    assert!(laundry.power_curve.run.is_none());
+    assert_eq!(laundry.generic_item.state[KEY_POWER].1, None);
+}
+
+/// Key of the internal state to dismiss the notification of a finished program.
+fn dismiss_key() -> String {
+    format!("{}dismiss", INTERNAL_STATE_PREFIX)
+}
+
+/// Time of day for the given timestamp, e.g. "14:35".
+fn format_time_of_day(timestamp: f64) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Duration like "1 h 20 min".
+fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.).round() as i64;
+    match minutes / 60 {
+        0 => format!("{} min", minutes),
+        hours => format!("{} h {} min", hours, minutes % 60),
+    }
 }
 
 fn init_map(generic_item: &GenericItem<LaundryConfig>) -> serde_json::Map<String, Value> {
@@ -18,7 +342,7 @@ fn init_map(generic_item: &GenericItem<LaundryConfig>) -> serde_json::Map<String
 }
 
 fn render_slider(generic_item: &GenericItem<LaundryConfig>) -> Option<(String, usize)> {
-    match generic_item.state.get("active") {
+    match generic_item.state.get(KEY_ACTIVE) {
         Some(state) => {
             if state.0 == "ON" {
                 Some(("slider-laundry".to_string(), 15))
@@ -30,6 +354,213 @@ fn render_slider(generic_item: &GenericItem<LaundryConfig>) -> Option<(String, u
     }
 }
 
-pub fn new() -> GenericItem<LaundryConfig> {
-    GenericItem::with_custom_functions(generic_translate_value, init_map, render_slider)
+/// Appliance like a washer, dryer or dish washer.
+///
+/// Program runs are detected from the power of the appliance. The remaining time is estimated
+/// from the last completed runs, which are kept across restarts and also learned from the
+/// history of the power.
+pub struct Laundry {
+    generic_item: GenericItem<LaundryConfig>,
+    power_curve: PowerCurve,
+}
+
+impl Laundry {
+    fn config(&self) -> LaundryConfig {
+        self.generic_item.config.clone().unwrap_or_default()
+    }
+
+    fn power(&self) -> Option<f64> {
+        parse_value(&self.generic_item.state.get(KEY_POWER)?.0)
+    }
+
+    /// Current phase of the program, e.g. "heating" or "finished".
+    fn phase(&self, now: f64, config: &LaundryConfig) -> String {
+        if self.power_curve.is_running(now, config) {
+            let power = self.power().unwrap_or(0.) as f32;
+            config
+                .phases
+                .iter()
+                .filter(|(min_power, _)| power >= *min_power)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, label)| label.to_string())
+                .unwrap_or("running".to_string())
+        } else if self.power_curve.is_finished(now, config) {
+            "finished".to_string()
+        } else {
+            "off".to_string()
+        }
+    }
+}
+
+impl Item for Laundry {
+    fn state_changed(&mut self, state_key: &str, new_state: &str) {
+        if state_key == dismiss_key() {
+            self.power_curve.finished_at = None;
+            return;
+        }
+        self.generic_item.state_changed(state_key, new_state);
+        if state_key == KEY_POWER {
+            if let Some(power) = self.power() {
+                let config = self.config();
+                self.power_curve
+                    .power_changed(power, history::now(), &config);
+            }
+        }
+    }
+
+    /// Initial states aren't changes, so they don't start a run. It's not known when the power
+    /// of an appliance that is already running went up.
+    fn initial_state(&mut self, state_key: &str, new_state: &str) {
+        self.generic_item.initial_state(state_key, new_state);
+    }
+
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        self.generic_item.set_configuration(config)
+    }
+
+    fn history_keys(&self) -> Vec<&str> {
+        vec![KEY_POWER]
+    }
+
+    /// Learn the runs of the past, e.g. while the app wasn't running.
+    fn history_received(&mut self, state_key: &str, datapoints: &[[f64; 2]]) {
+        if state_key == KEY_POWER {
+            let config = self.config();
+            self.power_curve
+                .learn_from_history(datapoints, self.power(), history::now(), &config);
+        }
+    }
+
+    fn set_smarthome_items(&mut self, items: &HashMap<String, SmartHomeItemInternal>) {
+        self.generic_item.set_smarthome_items(items);
+    }
+
+    /// The learned runs, so estimates are available right after restarting.
+    fn stored_state(&self) -> Option<Value> {
+        serde_json::to_value(StoredRuns {
+            learned: self.power_curve.learned.clone(),
+            learned_until: self.power_curve.learned_until,
+        })
+        .ok()
+    }
+
+    fn restore_state(&mut self, state: &Value) {
+        match serde_json::from_value::<StoredRuns>(state.clone()) {
+            Ok(StoredRuns {
+                mut learned,
+                learned_until,
+            }) => {
+                let num_old = learned.len().saturating_sub(MAX_LEARNED_RUNS);
+                learned.drain(..num_old);
+                self.power_curve.learned = learned;
+                self.power_curve.learned_until = learned_until;
+            }
+            Err(e) => error!("Failed to restore learned runs: {:?}", e),
+        }
+    }
+
+    fn render_slider(&self) -> Option<(String, usize)> {
+        let now = history::now();
+        match self.power_curve.is_running(now, &self.config()) {
+            true => Some(("slider-laundry".to_string(), 15)),
+            false => self.generic_item.render_slider(),
+        }
+    }
+
+    fn get_notification_status(&self) -> Option<NotificationStatus> {
+        if self.power_curve.is_finished(history::now(), &self.config()) {
+            Some(NotificationStatus {
+                color: "green".to_string(),
+                priority: 2,
+                num: 1,
+                label: "appliances finished".to_string(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// State of the generic item plus phase, remaining and finish time of the current program.
+    fn state_as_json(&self) -> serde_json::Map<String, Value> {
+        let now = history::now();
+        let mut map = self.generic_item.state_as_json();
+        map.insert(
+            "phase".to_string(),
+            Value::String(self.phase(now, &self.config())),
+        );
+        if let Some(remaining) = self.power_curve.remaining(now) {
+            map.insert(
+                "remaining".to_string(),
+                Value::String(format_duration(remaining)),
+            );
+            map.insert(
+                "finish_time".to_string(),
+                Value::String(format_time_of_day(now + remaining)),
+            );
+        }
+        map
+    }
+
+    fn render_fullscreen(
+        &self,
+        render_setting: &WidgetRenderSetting,
+        ctx: &mut egui::Context,
+    ) -> Vec<WidgetInteraction> {
+        let now = history::now();
+        let config = self.config();
+        ui::render_fullscreen_window(ctx, render_setting, |egui_ui| {
+            let mut requests = vec![];
+            let remaining = self.power_curve.remaining(now);
+            egui::Grid::new(render_setting.id.with("state"))
+                .striped(true)
+                .show(egui_ui, |egui_ui| {
+                    let rows = [
+                        ("Phase", self.phase(now, &config)),
+                        (
+                            "Power",
+                            self.power()
+                                .map(|p| format!("{:.0} W", p))
+                                .unwrap_or("n.a.".to_string()),
+                        ),
+                        (
+                            "Remaining",
+                            remaining
+                                .map(format_duration)
+                                .unwrap_or("unknown".to_string()),
+                        ),
+                        (
+                            "Finished at",
+                            remaining
+                                .map(|r| format_time_of_day(now + r))
+                                .or(self.power_curve.finished_at.map(format_time_of_day))
+                                .unwrap_or("unknown".to_string()),
+                        ),
+                        (
+                            "Learned programs",
+                            self.power_curve.learned.len().to_string(),
+                        ),
+                    ];
+                    for (label, value) in rows {
+                        egui_ui.label(label);
+                        egui_ui.label(value);
+                        egui_ui.end_row();
+                    }
+                });
+            if self.power_curve.finished_at.is_some() && egui_ui.button("Dismiss").clicked() {
+                requests.push(WidgetInteraction::state_change(&dismiss_key(), "true"));
+            }
+            requests
+        })
+    }
+}
+
+pub fn new() -> Laundry {
+    Laundry {
+        generic_item: GenericItem::with_custom_functions(
+            generic_translate_value,
+            init_map,
+            render_slider,
+        ),
+        power_curve: PowerCurve::default(),
+    }
 }
@@ -1,21 +1,52 @@
+use bevy_egui::egui::{self, RichText};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use serde_json::Value;
 
 use super::generic_item::{generic_render_slider, generic_translate_value, GenericItem};
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::WidgetRenderSetting;
 
-#[derive(Deserialize, Clone, Debug, Default)]
+// Switch disabling the automation, so ON means the lights are controlled manually.
+const KEY_DISABLE_AUTO: &str = "disable_auto";
+// Illuminance in lux below which the automation turns on the lights.
+const KEY_LUX_SET: &str = "lux_set";
+
+fn default_lux_presets() -> Vec<u32> {
+    vec![10, 20, 50, 100]
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
-pub struct LightAutoConfig {}
+pub struct LightAutoConfig {
+    /// Lux thresholds to choose from.
+    #[serde(default = "default_lux_presets")]
+    lux_presets: Vec<u32>,
+}
 
-fn init_map(generic_item: &GenericItem<LightAutoConfig>) -> serde_json::Map<String, Value> {
-    let auto_on = match generic_item.state.get("disable_auto") {
+fn is_auto_on(generic_item: &GenericItem<LightAutoConfig>) -> bool {
+    match generic_item.state.get(KEY_DISABLE_AUTO) {
         Some(state) => state.0 == "OFF",
         None => false,
+    }
+}
+
+/// Request enabling or disabling the automation.
+fn toggle_auto(generic_item: &GenericItem<LightAutoConfig>) -> WidgetInteraction {
+    let disable = match is_auto_on(generic_item) {
+        true => "ON",
+        false => "OFF",
     };
+    WidgetInteraction::state_change(KEY_DISABLE_AUTO, disable)
+}
+
+fn init_map(generic_item: &GenericItem<LightAutoConfig>) -> serde_json::Map<String, Value> {
+    let auto_on = is_auto_on(generic_item);
     let mut map = serde_json::Map::new();
 
     map.insert("d_auto_on".to_string(), Value::Bool(auto_on));
-    if let Some(lux_set_is) = generic_item.state.get("lux_set") {
+    if let Some(lux_set_is) = generic_item.state.get(KEY_LUX_SET) {
         map.insert(
             format!("lux_set_is_equal_{}", lux_set_is.0),
             Value::Bool(true),
@@ -24,6 +55,82 @@ fn init_map(generic_item: &GenericItem<LightAutoConfig>) -> serde_json::Map<Stri
     map
 }
 
+/// Badge in the room showing whether the automation is active.
+///
+/// Clicking the badge toggles the automation, the lux threshold is set in the fullscreen view.
+fn render_egui(
+    generic_item: &GenericItem<LightAutoConfig>,
+    render_position: (f32, f32),
+    render_setting: &WidgetRenderSetting,
+    context: &mut egui::Context,
+) -> Vec<WidgetInteraction> {
+    let mut requests = vec![];
+    let mut card_requests =
+        ui::render_info_card(context, render_setting, render_position, |egui_ui| {
+            let badge = match is_auto_on(generic_item) {
+                true => RichText::new("auto").strong().color(ui::DARK_GREEN),
+                false => RichText::new("auto").weak().strikethrough(),
+            };
+            if egui_ui
+                .add(egui::Label::new(badge).sense(egui::Sense::click()))
+                .clicked()
+            {
+                requests.push(toggle_auto(generic_item));
+            }
+        });
+    requests.append(&mut card_requests);
+    requests
+}
+
+fn render_fullscreen(
+    generic_item: &GenericItem<LightAutoConfig>,
+    render_setting: &WidgetRenderSetting,
+    context: &mut egui::Context,
+) -> Vec<WidgetInteraction> {
+    ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+        let mut requests = vec![];
+        let mut auto_on = is_auto_on(generic_item);
+        if egui_ui
+            .checkbox(&mut auto_on, "Automatic lighting")
+            .changed()
+        {
+            requests.push(toggle_auto(generic_item));
+        }
+
+        let presets = match &generic_item.config {
+            Some(config) => config.lux_presets.clone(),
+            None => default_lux_presets(),
+        };
+        let lux_set = generic_item
+            .state
+            .get(KEY_LUX_SET)
+            .and_then(|s| s.0.split(' ').next()?.parse::<f32>().ok());
+        egui_ui.label("Turn on below");
+        egui_ui.horizontal(|egui_ui| {
+            for preset in presets {
+                let selected = lux_set.map_or(false, |lux| lux == preset as f32);
+                if egui_ui
+                    .selectable_label(selected, format!("{} lx", preset))
+                    .clicked()
+                {
+                    requests.push(WidgetInteraction::state_change(
+                        KEY_LUX_SET,
+                        &preset.to_string(),
+                    ));
+                }
+            }
+        });
+        requests
+    })
+}
+
 pub(crate) fn new() -> GenericItem<LightAutoConfig> {
-    GenericItem::with_custom_functions(generic_translate_value, init_map, generic_render_slider)
+    let mut d = GenericItem::with_custom_functions(
+        generic_translate_value,
+        init_map,
+        generic_render_slider,
+    );
+    d.with_render_egui_f(render_egui);
+    d.with_fullscreen_f(render_fullscreen);
+    d
 }
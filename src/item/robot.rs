@@ -1,11 +1,28 @@
+use bevy::log::error;
+use bevy::prelude::{Color, Vec3};
+use bevy_egui::egui;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::hash_map::Iter;
 use std::collections::HashMap;
 
+use crate::events::{PositionModification, SceneModificationEvent, TintModification};
 use crate::item::generic_item::{generic_translate_value, GenericItem, INTERNAL_STATE_PREFIX};
+use crate::openhab::WidgetInteraction;
+use crate::ui;
+use crate::widget_settings::{EntityName, SceneModification, WidgetRenderSetting};
 
-#[derive(Deserialize, Clone, Debug, Default)]
+// Key of the item commands are sent to.
+const KEY_COMMAND: &str = "command";
+// Phase reported by the robot while cleaning.
+const PHASE_RUN: &str = "run";
+// Phase reported by the robot while it is in its dock.
+const PHASE_CHARGE: &str = "charge";
+// Tint of rooms and regions being cleaned.
+const CLEANING_COLOR: Color = Color::rgb(0.0, 0.15, 0.3);
+
+#[derive(Deserialize, JsonSchema, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RobotConfig {
     image: String,
@@ -13,31 +30,78 @@ pub struct RobotConfig {
     user_pmap_id: String,
     rooms: HashMap<String, String>,
     regions: HashMap<String, String>,
+    /// Entity in the scene for each room or region, highlighted while it is being cleaned.
+    #[serde(default)]
+    entities: HashMap<String, EntityName>,
+    /// Position of the dock in the scene.
+    dock: Option<[f32; 3]>,
+    /// Position in the scene of the origin of the robot's `x` and `y` coordinates.
+    map_origin: Option<[f32; 3]>,
+    /// Meters in the scene per unit of the robot's coordinates.
+    map_scale: Option<f32>,
 }
 
-fn init_map(generic_item: &GenericItem<RobotConfig>) -> serde_json::Map<String, Value> {
-    let is_home = match generic_item.state.get("phase") {
-        Some(state) => state.0 == "charge",
-        None => false,
+/// Key of the internal state remembering whether the given room or region is selected.
+fn select_key(name: &str) -> String {
+    format!("{}select_{}", INTERNAL_STATE_PREFIX, name)
+}
+
+fn is_selected(generic_item: &GenericItem<RobotConfig>, name: &str) -> bool {
+    generic_item
+        .state
+        .get(&select_key(name))
+        .map(|s| s.0 == "true")
+        .unwrap_or(false)
+}
+
+/// Command for cleaning the given list of room and region ids.
+fn clean_command(config: &RobotConfig, ids: &[String]) -> String {
+    format!(
+        "cleanRegions:{};{};{}",
+        config.pmap_id,
+        ids.join(","),
+        config.user_pmap_id
+    )
+}
+
+fn has_phase(generic_item: &GenericItem<RobotConfig>, phase: &str) -> bool {
+    generic_item
+        .state
+        .get("phase")
+        .map(|s| s.0 == phase)
+        .unwrap_or(false)
+}
+
+/// Position of the robot in the scene.
+///
+/// That's the reported location if the robot's coordinates can be mapped to the scene, its dock
+/// otherwise.
+fn position(generic_item: &GenericItem<RobotConfig>) -> Option<Vec3> {
+    let config = generic_item.config.as_ref()?;
+    let coordinate = |key: &str| generic_item.state.get(key)?.0.parse::<f32>().ok();
+    let location = match (config.map_origin, coordinate("x"), coordinate("y")) {
+        (Some(origin), Some(x), Some(y)) if !has_phase(generic_item, PHASE_CHARGE) => {
+            let scale = config.map_scale.unwrap_or(1.);
+            Some(Vec3::from(origin) + Vec3::new(x * scale, 0., y * scale))
+        }
+        _ => None,
     };
+    location.or(config.dock.map(Vec3::from))
+}
+
+fn init_map(generic_item: &GenericItem<RobotConfig>) -> serde_json::Map<String, Value> {
+    let is_home = has_phase(generic_item, PHASE_CHARGE);
     let mut map = serde_json::Map::new();
     if let Some(config) = &generic_item.config {
         map.insert("image".to_string(), Value::String(config.image.to_string()));
         map.insert("is_home".to_string(), Value::Bool(is_home));
 
-        let default = "false".to_string();
-
         let translate = |iter: Iter<String, String>| {
             let mut res: Vec<(String, String, bool)> = vec![];
             let mut selection: Vec<(String, String)> = vec![];
 
             for (name, id) in iter {
-                let select_value = generic_item
-                    .state
-                    .get(&format!("{}select_{}", INTERNAL_STATE_PREFIX, &name))
-                    .map(|s| &s.0)
-                    .unwrap_or(&default)
-                    == &"true";
+                let select_value = is_selected(generic_item, name);
                 if select_value {
                     selection.push((name.to_string(), id.to_string()));
                 }
@@ -66,16 +130,12 @@ fn init_map(generic_item: &GenericItem<RobotConfig>) -> serde_json::Map<String,
             serde_json::to_value(selection.len() > 0).unwrap(),
         );
 
-        let command_string = selection
+        let ids = selection
             .into_iter()
             .map(|(_, id)| id)
-            .collect::<Vec<String>>()
-            .join(",");
+            .collect::<Vec<String>>();
 
-        let clean_command = format!(
-            "cleanRegions:{};{};{}",
-            config.pmap_id, command_string, config.user_pmap_id
-        );
+        let clean_command = clean_command(config, &ids);
         map.insert(
             "clean_command".to_string(),
             serde_json::to_value(clean_command).unwrap(),
@@ -87,7 +147,7 @@ fn init_map(generic_item: &GenericItem<RobotConfig>) -> serde_json::Map<String,
 fn render_slider(generic_item: &GenericItem<RobotConfig>) -> Option<(String, usize)> {
     match generic_item.state.get("phase") {
         Some(state) => {
-            if state.0 != "charge" {
+            if state.0 != PHASE_CHARGE {
                 Some(("slider-robot".to_string(), 20))
             } else {
                 None
@@ -97,6 +157,118 @@ fn render_slider(generic_item: &GenericItem<RobotConfig>) -> Option<(String, usi
     }
 }
 
+/// Highlight the rooms and regions being cleaned and place the robot at its location.
+fn state_to_blender(
+    generic_item: &GenericItem<RobotConfig>,
+    entity_name: &EntityName,
+    modification: SceneModification,
+) -> Vec<SceneModificationEvent> {
+    let Some(config) = &generic_item.config else {
+        return vec![];
+    };
+    match modification {
+        SceneModification::Highlight() => {
+            let is_cleaned = has_phase(generic_item, PHASE_RUN)
+                && config
+                    .entities
+                    .iter()
+                    .any(|(name, entity)| entity == entity_name && is_selected(generic_item, name));
+            vec![SceneModificationEvent::TintModification(TintModification {
+                entity_name: entity_name.to_string(),
+                color: is_cleaned.then_some(CLEANING_COLOR),
+            })]
+        }
+        SceneModification::Position() => match position(generic_item) {
+            Some(position) => vec![SceneModificationEvent::PositionModification(
+                PositionModification {
+                    entity_name: entity_name.to_string(),
+                    position: Some(position),
+                },
+            )],
+            None => vec![],
+        },
+        _ => {
+            error!("Unsupported modification {:?} in Robot", modification);
+            vec![]
+        }
+    }
+}
+
+/// Control panel of the robot.
+///
+/// The selection of rooms and regions is kept as internal state of the widget, only the clean
+/// command is sent to the backend.
+fn render_fullscreen(
+    generic_item: &GenericItem<RobotConfig>,
+    render_setting: &WidgetRenderSetting,
+    context: &mut egui::Context,
+) -> Vec<WidgetInteraction> {
+    ui::render_fullscreen_window(context, render_setting, |egui_ui| {
+        let mut requests = vec![];
+        let Some(config) = &generic_item.config else {
+            return requests;
+        };
+
+        let get = |key: &str| {
+            generic_item
+                .state
+                .get(key)
+                .map(|s| s.0.to_string())
+                .unwrap_or("n.a.".to_string())
+        };
+        egui_ui.label(format!("Phase: {}", get("phase")));
+        egui_ui.label(format!("Battery: {} %", get("battery")));
+        egui_ui.horizontal(|egui_ui| {
+            for (label, command) in [("Start", "clean"), ("Pause", "pause"), ("Dock", "dock")] {
+                if egui_ui.button(label).clicked() {
+                    requests.push(WidgetInteraction::state_change(KEY_COMMAND, command));
+                }
+            }
+        });
+        egui_ui.separator();
+
+        let mut ids = vec![];
+        for (heading, names) in [("Rooms", &config.rooms), ("Regions", &config.regions)] {
+            if names.is_empty() {
+                continue;
+            }
+            egui_ui.label(heading);
+
+            // Sort by name, so the order doesn't change between frames.
+            let mut names = names.iter().collect::<Vec<(&String, &String)>>();
+            names.sort();
+            for (name, id) in names {
+                let mut selected = is_selected(generic_item, name);
+                if egui_ui.checkbox(&mut selected, name).changed() {
+                    requests.push(WidgetInteraction::state_change(
+                        &select_key(name),
+                        &selected.to_string(),
+                    ));
+                }
+                if selected {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+
+        if egui_ui
+            .add_enabled(!ids.is_empty(), egui::Button::new("Clean selection"))
+            .clicked()
+        {
+            requests.push(WidgetInteraction::state_change(
+                KEY_COMMAND,
+                &clean_command(config, &ids),
+            ));
+        }
+
+        requests
+    })
+}
+
 pub(crate) fn new() -> GenericItem<RobotConfig> {
-    GenericItem::with_custom_functions(generic_translate_value, init_map, render_slider)
+    let mut d =
+        GenericItem::with_custom_functions(generic_translate_value, init_map, render_slider);
+    d.with_fullscreen_f(render_fullscreen);
+    d.with_blender_f(state_to_blender);
+    d
 }
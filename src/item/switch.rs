@@ -1,6 +1,8 @@
 use crate::emoji;
 use crate::events::LightModification;
 use crate::events::SceneModificationEvent;
+use crate::item;
+use crate::item::ChangeStatus;
 use crate::item::Item;
 use crate::item::NotificationStatus;
 use crate::openhab::RequestedStateChangeFromWidget;
@@ -12,6 +14,7 @@ use crate::widget_settings::WidgetRenderSetting;
 
 use bevy_egui::egui;
 use bevy_egui::egui::Image;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
@@ -19,7 +22,7 @@ macro_rules! log {
     ( $( $t:tt )* ) => {};
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SwitchConfig {
     icon: String,
@@ -28,7 +31,8 @@ pub struct SwitchConfig {
 
 pub struct Switch {
     state: bool,
-    toggling: bool,
+    /// Status of the last toggle, until the backend reports a new state.
+    change_status: Option<ChangeStatus>,
     watts: Option<f32>,
     icon: String,
     icon_given: bool,
@@ -39,9 +43,9 @@ impl Switch {
     pub fn new() -> Switch {
         Switch {
             state: false,
-            toggling: false,
+            change_status: None,
             watts: None,
-            icon: "1F4A1".to_string(),
+            icon: emoji::LIGHT_BULB.to_string(),
             icon_given: false,
             label: None,
         }
@@ -49,21 +53,37 @@ impl Switch {
 }
 
 impl Item for Switch {
-    fn set_configuration(&mut self, config: &serde_json::Value) {
-        let config: SwitchConfig =
-            serde_json::from_value(config.clone()).expect("Failed to parse config for item");
+    fn set_configuration(&mut self, config: &serde_json::Value) -> serde_json::Result<()> {
+        let config: SwitchConfig = serde_json::from_value(config.clone())?;
         log!("Switch: using configuration {:?}", &config);
+        if emoji::get_emoji(&config.icon).is_none() {
+            return Err(serde::de::Error::custom(format!(
+                "unknown icon {:?}",
+                config.icon
+            )));
+        }
         self.icon = config.icon;
         self.icon_given = true;
         self.label = config.label;
+        Ok(())
     }
 
     fn state_changed(&mut self, key: &str, new_state: &str) {
         if key == "miliampere" {
             self.watts = Some(new_state.parse::<f32>().unwrap_or(0.) / 1000. * 230.);
         } else {
-            self.state = new_state != "OFF";
-            self.toggling = false;
+            self.state = !matches!(new_state, "OFF" | "NULL" | "UNDEF");
+            self.change_status = None;
+        }
+    }
+
+    fn is_optimistic(&self, state_key: &str) -> bool {
+        state_key != "miliampere"
+    }
+
+    fn change_status(&mut self, state_key: &str, status: ChangeStatus) {
+        if state_key != "miliampere" {
+            self.change_status = Some(status).filter(|s| *s != ChangeStatus::Confirmed);
         }
     }
 
@@ -99,12 +119,17 @@ impl Item for Switch {
                 color: "yellow".to_string(),
                 priority: 1,
                 num: 1,
+                label: "switches on".to_string(),
             })
         } else {
             None
         }
     }
 
+    fn scene_clicked(&self, widget_name: &str) -> Vec<WidgetInteraction> {
+        item::toggle_interaction(widget_name, self.state, None, None)
+    }
+
     fn render_egui(
         &self,
         render_position: (f32, f32),
@@ -123,10 +148,22 @@ impl Item for Switch {
             render_position,
             self.state,
             self.watts,
-            emoji::get_emoji(&self.icon),
+            self.change_status,
+            emoji::get_emoji(&self.icon).unwrap_or_else(emoji::light_bulb),
             on_color,
             None,
             None,
         )
     }
 }
+
+#[test]
+fn test_unknown_icon() {
+    let mut switch = Switch::new();
+    assert!(switch
+        .set_configuration(&serde_json::json!({"icon": "E150"}))
+        .is_ok());
+    assert!(switch
+        .set_configuration(&serde_json::json!({"icon": "1F9FA"}))
+        .is_err());
+}
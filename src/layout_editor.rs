@@ -0,0 +1,493 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{PrimitiveTopology, VertexAttributeValues},
+        primitives::Aabb,
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_http_client::prelude::HttpRequest;
+use smooth_bevy_cameras::controllers::orbit::OrbitCameraController;
+
+use crate::auth::Auth;
+use crate::device_model::DeviceModel;
+use crate::picking::{ray_aabb_intersection, ray_triangle_intersection};
+use crate::widget_settings::{WidgetName, WIDGET_HEIGHT};
+
+/// File the edited configuration is downloaded as.
+#[cfg(target_arch = "wasm32")]
+const EXPORT_FILE_NAME: &str = "items.json";
+/// Path of the configuration in native builds, overwritten on export.
+#[cfg(not(target_arch = "wasm32"))]
+const EXPORT_PATH: &str = "assets/items.json";
+
+const HANDLE_SIZE: f32 = 16.;
+
+/// Label and blender items of a widget, as edited in the side panel.
+struct WidgetDraft {
+    widget_name: WidgetName,
+    label: String,
+    /// Entity and comma separated list of scene modifications.
+    blender_items: Vec<(String, String)>,
+    new_entity: String,
+}
+
+/// Edit mode for placing widgets in the 3D scene and changing their configuration.
+///
+/// Widgets of the current view get a handle that can be dragged. While dragging, the position is
+/// snapped to the surface under the cursor. Widgets without a position are placed by clicking
+/// where they should be dropped. Changes are applied to the running configuration, which can be
+/// exported as JSON.
+#[derive(Default, Resource)]
+pub(crate) struct LayoutEditor {
+    pub(crate) active: bool,
+    draft: Option<WidgetDraft>,
+    /// Widget whose handle is being dragged.
+    dragging: Option<WidgetName>,
+    /// Widget without a position, placed at the next click into the scene.
+    placing: Option<WidgetName>,
+    /// Result of the last export.
+    status: Option<String>,
+}
+
+/// Whether the layout is being edited, in which case picking scene objects is disabled.
+pub(crate) fn is_editing(editor: Res<LayoutEditor>) -> bool {
+    editor.active
+}
+
+/// Split a comma separated list of scene modifications, e.g. "Energy, Color".
+fn parse_modifications(modifications: &str) -> Vec<String> {
+    modifications
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[test]
+fn test_parse_modifications() {
+    assert_eq!(
+        parse_modifications("Energy, Color,,"),
+        vec!["Energy".to_string(), "Color".to_string()]
+    );
+    assert_eq!(parse_modifications(" "), Vec::<String>::new());
+}
+
+/// Distance along the ray to the nearest triangle of the mesh, all in the mesh's local space.
+///
+/// Only triangle lists, like the meshes of glTF scenes, are hit.
+fn ray_mesh_intersection(origin: Vec3, direction: Vec3, mesh: &Mesh) -> Option<f32> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().collect::<Vec<_>>(),
+        None => (0..positions.len()).collect(),
+    };
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            let vertex = |i: usize| positions.get(triangle[i]).map(|&p| Vec3::from(p));
+            ray_triangle_intersection(origin, direction, [vertex(0)?, vertex(1)?, vertex(2)?])
+        })
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+/// Point on the surface under the given ray, or on the floor if there is no mesh.
+///
+/// The ray is tested against the triangles of meshes whose bounding box it hits. Surfaces above
+/// the height widgets are shown at, e.g. the ceiling, are ignored.
+fn snap_to_surface<'a>(
+    ray: Ray3d,
+    meshes: impl Iterator<Item = (&'a Mesh, &'a Aabb, &'a GlobalTransform)>,
+) -> Option<Vec3> {
+    let direction = *ray.direction;
+    let mut nearest = ray
+        .intersect_plane(Vec3::ZERO, Plane3d::new(Vec3::Y))
+        .map(|distance| ray.get_point(distance));
+    let mut nearest_distance = nearest.map(|point| point.distance(ray.origin));
+
+    for (mesh, aabb, transform) in meshes {
+        let local_to_world = transform.compute_matrix();
+        let world_to_local = local_to_world.inverse();
+        let local_origin = world_to_local.transform_point3(ray.origin);
+        let local_direction = world_to_local.transform_vector3(direction);
+        if ray_aabb_intersection(
+            local_origin,
+            local_direction,
+            aabb.min().into(),
+            aabb.max().into(),
+        )
+        .is_none()
+        {
+            continue;
+        }
+        let Some(distance) = ray_mesh_intersection(local_origin, local_direction, mesh) else {
+            continue;
+        };
+        let point = local_to_world.transform_point3(local_origin + local_direction * distance);
+        if point.y > WIDGET_HEIGHT {
+            continue;
+        }
+        let distance = point.distance(ray.origin);
+        if nearest_distance.map_or(true, |nearest| distance < nearest) {
+            nearest = Some(point);
+            nearest_distance = Some(distance);
+        }
+    }
+    nearest
+}
+
+#[test]
+fn test_snap_to_surface() {
+    use bevy::render::render_asset::RenderAssetUsages;
+
+    // A triangle at a height of 1, with a square bounding box
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![[0., 1., 0.], [2., 1., 0.], [0., 1., 2.]],
+    );
+    let aabb = mesh.compute_aabb().unwrap();
+    let transform = GlobalTransform::default();
+    let snap = |x: f32, z: f32| {
+        let ray = Ray3d::new(Vec3::new(x, 5., z), Vec3::NEG_Y);
+        snap_to_surface(ray, [(&mesh, &aabb, &transform)].into_iter())
+    };
+
+    assert_eq!(snap(0.5, 0.5), Some(Vec3::new(0.5, 1., 0.5)));
+    // Within the bounding box, but next to the triangle
+    assert_eq!(snap(1.5, 1.5), Some(Vec3::new(1.5, 0., 1.5)));
+}
+
+impl LayoutEditor {
+    fn select(&mut self, device_model: &DeviceModel, widget_name: &str) {
+        let Some(widget_config) = device_model
+            .configuration()
+            .and_then(|configuration| configuration.widget(widget_name))
+        else {
+            return;
+        };
+        let mut blender_items = widget_config
+            .blender_items
+            .iter()
+            .map(|(entity, modifications)| (entity.to_string(), modifications.join(", ")))
+            .collect::<Vec<_>>();
+        blender_items.sort();
+        if self.placing.as_deref() != Some(widget_name) {
+            self.placing = None;
+        }
+        self.draft = Some(WidgetDraft {
+            widget_name: widget_name.to_string(),
+            label: widget_config.label.clone().unwrap_or_default(),
+            blender_items,
+            new_entity: String::new(),
+        });
+    }
+
+    /// Export the configuration, to disk (native) or as download (wasm).
+    fn export(&mut self, device_model: &DeviceModel) {
+        let result = match device_model.configuration().map(|c| c.to_json()) {
+            Some(Ok(data)) => save(&data),
+            Some(Err(e)) => Err(format!("Failed to serialize configuration: {}", e)),
+            None => Err("No configuration loaded".to_string()),
+        };
+        self.status = Some(match result {
+            Ok(status) => status,
+            Err(e) => {
+                error!("{}", e);
+                e
+            }
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(data: &str) -> Result<String, String> {
+    // Keep the previous version, e.g. for hand-written comments or formatting
+    if std::path::Path::new(EXPORT_PATH).exists() {
+        let backup = format!("{}.bak", EXPORT_PATH);
+        std::fs::copy(EXPORT_PATH, &backup)
+            .map_err(|e| format!("Failed to back up {}: {}", EXPORT_PATH, e))?;
+    }
+    std::fs::write(EXPORT_PATH, data)
+        .map_err(|e| format!("Failed to write {}: {}", EXPORT_PATH, e))?;
+    Ok(format!("Saved {}", EXPORT_PATH))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save(data: &str) -> Result<String, String> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(data));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("application/json");
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(|e| format!("Failed to create file: {:?}", e))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)
+        .map_err(|e| format!("Failed to create file: {:?}", e))?;
+
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|element| element.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        .ok_or("Failed to start download".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(EXPORT_FILE_NAME);
+    anchor.click();
+    let _ = web_sys::Url::revoke_object_url(&url);
+    Ok(format!("Downloaded {}", EXPORT_FILE_NAME))
+}
+
+/// Render the layout editor: the button to start editing, drag handles for the widgets of the
+/// current view and the side panel for the selected widget.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn edit_layout(
+    mut editor: ResMut<LayoutEditor>,
+    mut device_model: ResMut<DeviceModel>,
+    mut contexts: EguiContexts,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut camera_controller: Query<&mut OrbitCameraController>,
+    meshes: Query<(&Handle<Mesh>, &Aabb, &GlobalTransform, &InheritedVisibility)>,
+    mesh_assets: Res<Assets<Mesh>>,
+    mut ev_request: EventWriter<HttpRequest>,
+    auth: Res<Auth>,
+) {
+    let ctx = contexts.ctx_mut();
+    if !editor.active {
+        egui::Area::new(egui::Id::new("layout_editor_toggle"))
+            .anchor(egui::Align2::RIGHT_TOP, [-10., 10.])
+            .show(ctx, |ui| {
+                if ui.button("✏ Edit layout").clicked() {
+                    editor.active = true;
+                }
+            });
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+
+    // Drag handles of the widgets placed in the current view
+    let widget_names = device_model
+        .current_view
+        .as_ref()
+        .and_then(|view| device_model.views.get(view))
+        .cloned()
+        .unwrap_or_default();
+    let mut dragged = None;
+    for widget_name in &widget_names {
+        let Some(viewport_position) = device_model
+            .widget_position(widget_name)
+            .and_then(|position| camera.world_to_viewport(camera_transform, position))
+        else {
+            continue;
+        };
+        let selected = editor.draft.as_ref().map(|draft| &draft.widget_name) == Some(widget_name);
+        egui::Area::new(egui::Id::new(("layout_handle", widget_name)))
+            .order(egui::Order::Foreground)
+            .fixed_pos([
+                viewport_position.x - HANDLE_SIZE / 2.,
+                viewport_position.y - HANDLE_SIZE / 2.,
+            ])
+            .show(ctx, |ui| {
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::vec2(HANDLE_SIZE, HANDLE_SIZE),
+                    egui::Sense::click_and_drag(),
+                );
+                let color = match selected {
+                    true => egui::Color32::YELLOW,
+                    false => egui::Color32::WHITE,
+                };
+                ui.painter().circle(
+                    rect.center(),
+                    HANDLE_SIZE / 2.,
+                    color,
+                    (1., egui::Color32::BLACK),
+                );
+                let response = response.on_hover_text(widget_name);
+                if response.clicked() || response.drag_started() {
+                    editor.select(&device_model, widget_name);
+                }
+                if response.dragged() {
+                    dragged = Some(widget_name.to_string());
+                }
+            });
+    }
+
+    let surface_at = |pointer: egui::Pos2| {
+        camera
+            .viewport_to_world(camera_transform, Vec2::new(pointer.x, pointer.y))
+            .and_then(|ray| {
+                snap_to_surface(
+                    ray,
+                    meshes
+                        .iter()
+                        .filter(|(_, _, _, visibility)| visibility.get())
+                        .filter_map(|(mesh, aabb, transform, _)| {
+                            Some((mesh_assets.get(mesh)?, aabb, transform))
+                        }),
+                )
+            })
+    };
+
+    // Snap the dragged widget to the surface under the cursor
+    if let (Some(widget_name), Some(pointer)) = (&dragged, ctx.pointer_interact_pos()) {
+        if let Some(position) = surface_at(pointer) {
+            device_model.move_widget(widget_name, position);
+        }
+    }
+
+    // Drop the widget being placed where the scene was clicked
+    let scene_clicked =
+        ctx.input(|input| input.pointer.primary_clicked()) && !ctx.is_pointer_over_area();
+    if let (Some(widget_name), Some(pointer)) = (
+        editor.placing.clone().filter(|_| scene_clicked),
+        ctx.pointer_interact_pos(),
+    ) {
+        if let Some(position) = surface_at(pointer) {
+            device_model.place_widget(&widget_name, position);
+            editor.placing = None;
+        }
+    }
+    // Don't move the camera while dragging a widget
+    if editor.dragging != dragged {
+        for mut controller in &mut camera_controller {
+            controller.enabled = dragged.is_none();
+        }
+        editor.dragging = dragged;
+    }
+
+    let mut apply = false;
+    let mut export = false;
+    let mut close = false;
+    let mut selected = None;
+    let mut placing = editor.placing.clone();
+    egui::SidePanel::right("layout_editor").show(ctx, |ui| {
+        ui.heading("Layout");
+        egui::ScrollArea::vertical()
+            .id_source("layout_widgets")
+            .max_height(200.)
+            .show(ui, |ui| {
+                let mut widget_names = widget_names.clone();
+                widget_names.sort();
+                for widget_name in widget_names {
+                    let is_selected =
+                        editor.draft.as_ref().map(|draft| &draft.widget_name) == Some(&widget_name);
+                    if ui.selectable_label(is_selected, &widget_name).clicked() {
+                        selected = Some(widget_name);
+                    }
+                }
+            });
+        ui.separator();
+
+        if let Some(draft) = &mut editor.draft {
+            ui.strong(&draft.widget_name);
+            match device_model.widget_position(&draft.widget_name) {
+                Some(position) => {
+                    ui.label(format!("left {:.2}, top {:.2}", position.x, -position.z));
+                }
+                None if placing.as_ref() == Some(&draft.widget_name) => {
+                    ui.horizontal(|ui| {
+                        ui.label("Click into the scene to place the widget.");
+                        if ui.small_button("Cancel").clicked() {
+                            placing = None;
+                        }
+                    });
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.label("Not placed");
+                        if ui.button("📍 Place").clicked() {
+                            placing = Some(draft.widget_name.clone());
+                        }
+                    });
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label("Label");
+                ui.text_edit_singleline(&mut draft.label);
+            });
+
+            ui.label("Blender items");
+            let mut removed = None;
+            egui::Grid::new("layout_blender_items").show(ui, |ui| {
+                for (i, (entity, modifications)) in draft.blender_items.iter_mut().enumerate() {
+                    ui.label(entity.as_str());
+                    ui.add(egui::TextEdit::singleline(modifications).hint_text("Energy, Color"));
+                    if ui.small_button("🗑").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = removed {
+                draft.blender_items.remove(i);
+            }
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut draft.new_entity).hint_text("Entity"));
+                if ui.button("Add").clicked() && !draft.new_entity.trim().is_empty() {
+                    let entity = std::mem::take(&mut draft.new_entity);
+                    draft
+                        .blender_items
+                        .push((entity.trim().to_string(), String::new()));
+                }
+            });
+            apply = ui.button("Apply").clicked();
+        } else {
+            ui.label("Select a widget to edit it.");
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            export = ui.button("Export JSON").clicked();
+            close = ui.button("Done").clicked();
+        });
+        if let Some(status) = &editor.status {
+            ui.label(status.as_str());
+        }
+    });
+
+    editor.placing = placing;
+
+    if let Some(widget_name) = selected {
+        editor.select(&device_model, &widget_name);
+    }
+
+    // Rebuild the widget from its changed configuration
+    if let Some(draft) = editor.draft.as_ref().filter(|_| apply) {
+        if let Some(mut configuration) = device_model.configuration().cloned() {
+            if let Some(widget_config) = configuration.widget_mut(&draft.widget_name) {
+                widget_config.label =
+                    Some(draft.label.trim().to_string()).filter(|l| !l.is_empty());
+                widget_config.blender_items = draft
+                    .blender_items
+                    .iter()
+                    .map(|(entity, modifications)| {
+                        (entity.to_string(), parse_modifications(modifications))
+                    })
+                    .collect();
+            }
+            for item in device_model.reload(&configuration) {
+                ev_request.send(crate::state_request(&auth, &item));
+            }
+        }
+    }
+
+    if export {
+        editor.export(&device_model);
+    }
+    if close {
+        editor.active = false;
+        editor.draft = None;
+        editor.placing = None;
+    }
+}
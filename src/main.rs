@@ -1,5 +1,7 @@
 use bevy::{
+    app::AppExit,
     diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::system::SystemParam,
     log::LogPlugin,
     pbr::{CascadeShadowConfigBuilder, DirectionalLightShadowMap, OpaqueRendererMethod},
     prelude::*,
@@ -14,28 +16,49 @@ use bevy_http_client::prelude::*;
 // use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use config_loader::SmartHomeConfigAsset;
 use device_model::DeviceModel;
-use ehttp::{Headers, Request};
 use errors::DeviceModelError;
-use events::{LightModification, SceneModificationEvent, SunModification};
+use events::{
+    EnergyFlowModification, LightModification, PositionModification, SceneModificationEvent,
+    SunModification, TintModification, VisibilityModification,
+};
 use smooth_bevy_cameras::{
     controllers::orbit::{OrbitCameraBundle, OrbitCameraController, OrbitCameraPlugin},
     LookTransformPlugin,
 };
 
+pub mod auth;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod commands;
 pub mod config;
 pub mod config_loader;
 pub mod device_model;
 pub mod emoji;
+pub mod energy_flow;
 pub mod errors;
 pub mod events;
+pub mod history;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod import;
 pub mod item;
+pub mod layout_editor;
+pub mod notifications;
 pub mod openhab;
+pub mod pending;
+pub mod picking;
 pub mod plot;
+pub mod scene;
+pub mod schema;
+pub mod slider;
+pub mod toasts;
 pub mod ui;
 pub mod utils;
 pub mod widget_settings;
 
-use crate::{openhab::OpenHabState, utils::scale_value};
+use crate::{
+    openhab::{OpenHabState, RequestedStateChange},
+    utils::scale_value,
+};
 use std::f32::consts::*;
 
 impl NetworkMessage for OpenHabState {
@@ -47,10 +70,8 @@ const OPENHAB_IP: &str = "192.168.178.20";
 const DEFAULT_LIGHT_INTENSITY: f32 = 50000.;
 const DEFAULT_ENV_INTENSITY: f32 = 500.;
 const CEILING_NAME: &str = "Plane.010";
-
-#[derive(Component)]
-
-struct EnergyFlow;
+// Interval in seconds in which the local history of item states and the widgets' state is stored.
+const HISTORY_SAVE_INTERVAL: f32 = 300.;
 
 #[derive(Component)]
 struct TestObject;
@@ -70,6 +91,11 @@ struct UiState {
 }
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(code) = cli::run(&std::env::args().skip(1).collect::<Vec<_>>()) {
+        std::process::exit(code);
+    }
+
     // Allow more HTTP clients, as each concurrent requests seems to be it's own client.
     // At boot up, we need one client per item to check for the state.
     let mut http_client_settings = HttpClientSetting::default();
@@ -84,14 +110,31 @@ fn main() {
         .insert_resource(http_client_settings)
         .init_resource::<UiState>()
         .init_resource::<DeviceModel>()
+        .init_resource::<picking::PickingState>()
+        .init_resource::<energy_flow::EnergyFlows>()
+        .init_resource::<commands::CommandQueue>()
+        .init_resource::<auth::Auth>()
+        .init_resource::<toasts::Toasts>()
+        .init_resource::<layout_editor::LayoutEditor>()
         .add_event::<LightModification>()
         .add_event::<SunModification>()
+        .add_event::<EnergyFlowModification>()
+        .add_event::<TintModification>()
+        .add_event::<PositionModification>()
+        .add_event::<VisibilityModification>()
+        .add_event::<RequestedStateChange>()
         .add_plugins((
-            DefaultPlugins.set(LogPlugin {
-                filter: "info,bevy_eventwork=debug,bevy_eventwork_mod_websockets=debug".into(),
-                level: bevy::log::Level::DEBUG,
-                update_subscriber: None,
-            }),
+            DefaultPlugins
+                .set(LogPlugin {
+                    filter: "info,bevy_eventwork=debug,bevy_eventwork_mod_websockets=debug".into(),
+                    level: bevy::log::Level::DEBUG,
+                    update_subscriber: None,
+                })
+                // Reload items.json when it changes (native only, see Cargo.toml)
+                .set(AssetPlugin {
+                    watch_for_changes_override: Some(true),
+                    ..default()
+                }),
             FrameTimeDiagnosticsPlugin,
             EguiPlugin,
             bevy_eventwork::EventworkPlugin::<WebSocketProvider, bevy::tasks::TaskPool>::default(),
@@ -117,15 +160,46 @@ fn main() {
                 animate_sun,
                 animate_lights,
                 animate_objects,
-                animate_paths,
+                (
+                    energy_flow::update_energy_flows,
+                    energy_flow::resolve_energy_flow_paths,
+                    energy_flow::spawn_energy_flow_particles,
+                    energy_flow::animate_paths,
+                )
+                    .chain(),
+                (
+                    scene::apply_tints,
+                    scene::apply_positions,
+                    scene::apply_visibility,
+                ),
                 ui_example_system,
+                notifications::render_notifications,
+                slider::render_slider_strip,
+                picking::pick_scene_objects
+                    .after(ui_example_system)
+                    .run_if(not(layout_editor::is_editing)),
+                picking::highlight_hovered.after(picking::pick_scene_objects),
+                (
+                    commands::send_commands
+                        .after(ui_example_system)
+                        .after(picking::pick_scene_objects),
+                    commands::handle_command_results,
+                ),
+                (
+                    toasts::render_toasts,
+                    layout_editor::edit_layout.after(ui_example_system),
+                ),
+                auth::login_prompt.run_if(auth::needs_login),
                 handle_network_events,
                 handle_connect,
                 handle_state_change,
                 handle_state_query_response,
-                setup_config,
+                handle_http_errors,
+                (setup_config, reload_config),
+                save_history,
             ),
         )
+        .add_systems(Last, save_history_on_exit)
         .listen_for_message::<OpenHabState, WebSocketProvider>()
         .run();
 }
@@ -133,8 +207,6 @@ fn main() {
 fn setup(
     mut ui_state: ResMut<UiState>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut contexts: EguiContexts,
     asset_server: Res<AssetServer>,
 ) {
@@ -182,23 +254,6 @@ fn setup(
     });
 
     ui_state.config = asset_server.load("items.json");
-
-    let energy_flow_material = materials.add(StandardMaterial {
-        base_color: Color::RED,
-        emissive: Color::RED,
-        reflectance: 0.,
-        ..default()
-    });
-
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Sphere::default().mesh().ico(3).unwrap()),
-            material: energy_flow_material.clone(),
-            transform: Transform::from_xyz(0.0, 2.0, -2.6).with_scale(Vec3::splat(0.1)),
-            ..default()
-        },
-        EnergyFlow,
-    ));
 }
 
 fn handle_connect(
@@ -206,12 +261,13 @@ fn handle_connect(
     net: ResMut<Network<WebSocketProvider>>,
     settings: Res<NetworkSettings>,
     task_pool: Res<EventworkRuntime<TaskPool>>,
+    auth: Res<auth::Auth>,
 ) {
-    if ui_state.connection_status == "" {
+    if ui_state.connection_status == "" && auth.ready {
         ui_state.connection_status = "Connecting".to_string();
         // Connect Websocket for Smart Home updates
         net.connect(
-            url::Url::parse(&format!(
+            auth.websocket_url(&format!(
                 "ws://{}:8080/ws?topic=smarthome/items/*/*",
                 OPENHAB_IP
             ))
@@ -224,17 +280,40 @@ fn handle_connect(
 
 /// Receive HTTP replies from calls made using the HTTP client.
 ///
-/// Those include state requests as well as commands sent requesting for item changes.
+/// Those are state requests and history requests. Commands are sent and handled separately, see
+/// [`commands`].
 fn handle_state_query_response(
     mut device_model: ResMut<DeviceModel>,
     mut ev_resp: EventReader<HttpResponse>,
-    mut ev_light_modification: EventWriter<LightModification>,
-    mut ev_sun_modification: EventWriter<SunModification>,
+    mut ev_scene_modification: SceneModificationWriters,
+    mut toasts: ResMut<toasts::Toasts>,
+    mut auth: ResMut<auth::Auth>,
 ) {
     for response in ev_resp.read() {
         // Format of the URL: http://192.168.178.20:8080/rest/items/OutdoorTemperature/state
-        // Another format:    http://192.168.178.20:8080/rest/items/zimmer_2_steckdose
         info!("Received HTTP response for state request: {:?}", response);
+
+        if !response.ok {
+            error!("Request {} failed: {}", response.url, response.status);
+            if auth::is_rejected(response.status) {
+                auth.logout();
+            }
+            toasts.error(&format!(
+                "Request {} failed: {} {}",
+                response.url, response.status, response.status_text
+            ));
+            continue;
+        }
+
+        // History requested for charts
+        if response.url.contains("/rest/persistence/items/") {
+            match openhab::parse_persistence_response(&response.bytes) {
+                Ok((item, datapoints)) => device_model.history_received(&item, datapoints),
+                Err(e) => error!("Handling persistence response failed: {:?}", e),
+            }
+            continue;
+        }
+
         let new_state = String::from_utf8(response.bytes.clone());
         if let Some(item) = response.url.strip_suffix("/state") {
             if let Some(item) = item.split("/").last() {
@@ -242,8 +321,7 @@ fn handle_state_query_response(
                     info!("Received state query response: {} <- {}", item, new_state);
                     match device_model.state_changed(item, &new_state) {
                         Ok(scene_modifications) => register_scene_modifications(
-                            &mut ev_light_modification,
-                            &mut ev_sun_modification,
+                            &mut ev_scene_modification,
                             scene_modifications,
                         ),
                         Err(e) => error!("Handling state query response failed: {:?}", e),
@@ -254,6 +332,17 @@ fn handle_state_query_response(
     }
 }
 
+/// Show requests of the HTTP client that failed without a response, e.g. if the backend is down.
+fn handle_http_errors(
+    mut ev_error: EventReader<HttpResponseError>,
+    mut toasts: ResMut<toasts::Toasts>,
+) {
+    for ev in ev_error.read() {
+        error!("HTTP request failed: {}", ev.err);
+        toasts.error(&format!("Request failed: {}", ev.err));
+    }
+}
+
 fn animate_sun(
     ui_state: ResMut<UiState>,
     mut sun_events: EventReader<SunModification>,
@@ -306,12 +395,6 @@ fn animate_sun(
     }
 }
 
-fn animate_paths(mut query: Query<&mut Transform, With<EnergyFlow>>, time: Res<Time>) {
-    for mut transform in &mut query {
-        transform.translation = Vec3::new(1.0 * (time.elapsed_seconds() % 5.0), 3.0, 0.0);
-    }
-}
-
 fn animate_lights(
     mut point_light: Query<(&Name, &mut PointLight)>,
     mut spot_light: Query<(&Name, &mut SpotLight)>,
@@ -355,8 +438,10 @@ fn animate_objects(
     mut query: Query<(&Name, &mut Handle<StandardMaterial>)>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut ev_request: EventWriter<HttpRequest>,
+    auth: Res<auth::Auth>,
 ) {
-    if !ui_state.replaced_ceiling_material {
+    // Wait for the credentials, so the initial state can be requested
+    if !ui_state.replaced_ceiling_material && auth.ready {
         for (name, material) in query.iter_mut() {
             if name.as_str() == CEILING_NAME {
                 println!(
@@ -378,13 +463,7 @@ fn animate_objects(
                 // Get a list of all  items and fetch the initial state for all of them.
                 for item in device_model.get_items() {
                     info!("Requesting state of item: {}", item);
-                    let request = HttpClient::new()
-                        .get(&format!(
-                            "http://{}:8080/rest/items/{}/state",
-                            OPENHAB_IP, item
-                        ))
-                        .build();
-                    ev_request.send(request);
+                    ev_request.send(state_request(&auth, item));
                 }
             }
         }
@@ -397,7 +476,10 @@ fn ui_example_system(
     diagnostics: Res<DiagnosticsStore>,
     mut contexts: EguiContexts,
     camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut ev_state_change: EventWriter<RequestedStateChange>,
     mut ev_request: EventWriter<HttpRequest>,
+    mut ev_scene_modification: SceneModificationWriters,
+    mut auth: ResMut<auth::Auth>,
 ) {
     // Determine FPS from diagnostics data
     let fps = diagnostics
@@ -420,32 +502,56 @@ fn ui_example_system(
         }
         ui.label(ui_state.connection_status.clone());
         ui.label(format!("Num updates {}", ui_state.num_updates));
+        auth::logout_button(ui, &mut auth);
     });
 
+    // Widgets with errors in their configuration are not shown, so list the errors instead
+    let config_errors = device_model.config_errors();
+    if !config_errors.is_empty() {
+        egui::Window::new(format!("Configuration errors ({})", config_errors.len()))
+            .id(egui::Id::new("config_errors"))
+            .show(contexts.ctx_mut(), |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(300.)
+                    .show(ui, |ui| {
+                        for e in config_errors {
+                            ui.label(egui::RichText::new(&e.path).monospace());
+                            ui.colored_label(egui::Color32::RED, e.kind.to_string());
+                        }
+                    });
+            });
+    }
+
     // Get camera for viewport position calculations.
     let (camera, camera_global_transform) = camera.single();
 
-    // Render widgets and forward HTTP call events generated by widgets
+    // Render widgets and forward state changes requested by widgets
     match device_model.render(contexts.ctx_mut(), camera, camera_global_transform) {
         Ok(requested_state_changes) => {
-            for requested_state_change in requested_state_changes {
-                let request = Request {
-                    method: "POST".to_string(),
-                    url: format!(
-                        "http://{}:8080/rest/items/{}",
-                        OPENHAB_IP, requested_state_change.item
-                    ),
-                    body: requested_state_change.value.as_bytes().to_vec(),
-                    headers: Headers::new(&[("Content-Type", "text/plain")]),
-                    #[cfg(target_arch = "wasm32")]
-                    mode: ehttp::Mode::Cors,
-                };
-
-                ev_request.send(HttpClient::new().request(request).build());
-            }
+            ev_state_change.send_batch(requested_state_changes);
         }
         Err(e) => error!("Failed to render current view: {:?}", e),
     }
+
+    // Scene modifications requested while rendering, e.g. previews
+    register_scene_modifications(
+        &mut ev_scene_modification,
+        device_model.take_scene_changes(),
+    );
+
+    // Fetch history for charts from the backend's persistence service
+    for (item, range) in device_model.take_history_requests() {
+        info!("Requesting {} history of item: {}", range.label(), item);
+        let request = HttpClient::new()
+            .request(auth.get(&format!(
+                "http://{}:8080/rest/persistence/items/{}?starttime={}",
+                OPENHAB_IP,
+                item,
+                range.start_time()
+            )))
+            .build();
+        ev_request.send(request);
+    }
 }
 
 fn handle_network_events(
@@ -472,21 +578,42 @@ fn handle_network_events(
     }
 }
 
+/// Event writers for all kinds of scene modifications.
+#[derive(SystemParam)]
+struct SceneModificationWriters<'w> {
+    light: EventWriter<'w, LightModification>,
+    sun: EventWriter<'w, SunModification>,
+    energy_flow: EventWriter<'w, EnergyFlowModification>,
+    tint: EventWriter<'w, TintModification>,
+    position: EventWriter<'w, PositionModification>,
+    visibility: EventWriter<'w, VisibilityModification>,
+}
+
 /// Registers the given scene modifications by sending them as events.
 fn register_scene_modifications(
-    ev_light_modification: &mut EventWriter<LightModification>,
-    ev_sun_modification: &mut EventWriter<SunModification>,
+    ev_scene_modification: &mut SceneModificationWriters,
     modifications: Vec<SceneModificationEvent>,
 ) {
     for event in modifications {
         match event {
             SceneModificationEvent::LightModification(ev) => {
-                ev_light_modification.send(ev);
+                ev_scene_modification.light.send(ev);
             }
             SceneModificationEvent::SunModification(ev) => {
-                ev_sun_modification.send(ev);
+                ev_scene_modification.sun.send(ev);
+            }
+            SceneModificationEvent::EnergyFlowModification(ev) => {
+                ev_scene_modification.energy_flow.send(ev);
+            }
+            SceneModificationEvent::TintModification(ev) => {
+                ev_scene_modification.tint.send(ev);
+            }
+            SceneModificationEvent::PositionModification(ev) => {
+                ev_scene_modification.position.send(ev);
+            }
+            SceneModificationEvent::VisibilityModification(ev) => {
+                ev_scene_modification.visibility.send(ev);
             }
-            _ => error!("Unsupported scene modification: {:?}", event),
         }
     }
 }
@@ -500,18 +627,15 @@ fn register_scene_modifications(
 fn handle_state_change(
     mut new_messages: EventReader<NetworkData<OpenHabState>>,
     mut ui_state: ResMut<UiState>,
-    mut ev_light_modification: EventWriter<LightModification>,
-    mut ev_sun_modification: EventWriter<SunModification>,
+    mut ev_scene_modification: SceneModificationWriters,
     mut device_model: ResMut<DeviceModel>,
 ) {
     for new_message in new_messages.read() {
         ui_state.num_updates += 1;
         match device_model.backend_state_changed(&new_message.topic, &new_message.payload) {
-            Ok(device_modifications) => register_scene_modifications(
-                &mut ev_light_modification,
-                &mut ev_sun_modification,
-                device_modifications,
-            ),
+            Ok(device_modifications) => {
+                register_scene_modifications(&mut ev_scene_modification, device_modifications)
+            }
             Err(DeviceModelError::ItemNotFound(e)) => {
                 debug!("Received update for unknown item: {:?}", e)
             }
@@ -523,6 +647,8 @@ fn handle_state_change(
 fn setup_config(
     mut device_model: ResMut<DeviceModel>,
     mut ev_request: EventWriter<HttpRequest>,
+    mut command_queue: ResMut<commands::CommandQueue>,
+    mut auth: ResMut<auth::Auth>,
     ui_state: Res<UiState>,
     config: Res<Assets<config_loader::SmartHomeConfigAsset>>,
 ) {
@@ -530,6 +656,79 @@ fn setup_config(
         let config = config.get(&ui_state.config).clone();
         if let Some(config) = config {
             device_model.parse(&config.config);
+            command_queue.retry_policy = config.config.retry.clone();
+            // In the browser, credentials are entered by the user instead
+            if !cfg!(target_arch = "wasm32") {
+                auth.config = config.config.auth.clone();
+                auth.ready = true;
+            }
+            device_model.load_state();
+        }
+    }
+}
+
+/// Request for the current state of the given item.
+pub(crate) fn state_request(auth: &auth::Auth, item: &str) -> HttpRequest {
+    HttpClient::new()
+        .request(auth.get(&format!(
+            "http://{}:8080/rest/items/{}/state",
+            OPENHAB_IP, item
+        )))
+        .build()
+}
+
+/// Apply changes of the configuration while running.
+///
+/// Only widgets whose configuration changed are rebuilt. The state of items that haven't been
+/// seen before is requested from the backend.
+fn reload_config(
+    mut ev_asset: EventReader<AssetEvent<config_loader::SmartHomeConfigAsset>>,
+    mut device_model: ResMut<DeviceModel>,
+    mut ev_request: EventWriter<HttpRequest>,
+    mut command_queue: ResMut<commands::CommandQueue>,
+    mut auth: ResMut<auth::Auth>,
+    ui_state: Res<UiState>,
+    config: Res<Assets<config_loader::SmartHomeConfigAsset>>,
+) {
+    for event in ev_asset.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        if *id != ui_state.config.id() || !device_model.initialized {
+            continue;
         }
+        let Some(config) = config.get(*id) else {
+            continue;
+        };
+
+        info!("Configuration changed, reloading");
+        let unknown_items = device_model.reload(&config.config);
+        command_queue.retry_policy = config.config.retry.clone();
+        if !cfg!(target_arch = "wasm32") {
+            auth.config = config.config.auth.clone();
+        }
+
+        for item in unknown_items {
+            info!("Requesting state of item: {}", item);
+            ev_request.send(state_request(&auth, &item));
+        }
+    }
+}
+
+/// Periodically store the local history of item states and the state kept by widgets.
+fn save_history(device_model: Res<DeviceModel>, time: Res<Time>, mut last_save: Local<f32>) {
+    if device_model.initialized && time.elapsed_seconds() - *last_save > HISTORY_SAVE_INTERVAL {
+        *last_save = time.elapsed_seconds();
+        device_model.save_state();
+    }
+}
+
+/// Store the local history of item states and the state kept by widgets when the app is closed.
+///
+/// Browsers don't give us a chance to do that when the tab is closed, there we rely on the
+/// periodic saves.
+fn save_history_on_exit(device_model: Res<DeviceModel>, mut ev_exit: EventReader<AppExit>) {
+    if ev_exit.read().last().is_some() && device_model.initialized {
+        device_model.save_state();
     }
 }
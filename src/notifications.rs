@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContexts,
+};
+use smooth_bevy_cameras::LookTransform;
+
+use crate::device_model::DeviceModel;
+use crate::item::NotificationStatus;
+use crate::widget_settings::{ViewName, WidgetName};
+
+/// Notification status of a single widget.
+#[derive(Debug)]
+pub(crate) struct Notification {
+    pub(crate) view_name: ViewName,
+    pub(crate) widget_name: WidgetName,
+    /// Label of the widget, or its name if it doesn't have one.
+    pub(crate) label: String,
+    pub(crate) room: Option<String>,
+    pub(crate) status: NotificationStatus,
+}
+
+/// Notifications with the same label, added up.
+#[derive(Debug, PartialEq)]
+pub(crate) struct NotificationSummary<'a> {
+    pub(crate) label: &'a str,
+    /// Color of the notification with the highest priority.
+    pub(crate) color: &'a str,
+    pub(crate) priority: u8,
+    pub(crate) num: u32,
+    pub(crate) widgets: Vec<(&'a ViewName, &'a WidgetName, &'a str)>,
+}
+
+/// Group notifications by their label, highest priority first.
+pub(crate) fn summarize<'a>(
+    notifications: impl Iterator<Item = &'a Notification>,
+) -> Vec<NotificationSummary<'a>> {
+    let mut summaries: Vec<NotificationSummary> = vec![];
+    for notification in notifications {
+        let status = &notification.status;
+        let widget = (
+            &notification.view_name,
+            &notification.widget_name,
+            notification.label.as_str(),
+        );
+
+        match summaries.iter_mut().find(|s| s.label == status.label) {
+            Some(summary) => {
+                if status.priority > summary.priority {
+                    summary.priority = status.priority;
+                    summary.color = &status.color;
+                }
+                summary.num += status.num;
+                summary.widgets.push(widget);
+            }
+            None => summaries.push(NotificationSummary {
+                label: &status.label,
+                color: &status.color,
+                priority: status.priority,
+                num: status.num,
+                widgets: vec![widget],
+            }),
+        }
+    }
+
+    summaries.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(b.num.cmp(&a.num))
+            .then(a.label.cmp(b.label))
+    });
+    for summary in &mut summaries {
+        summary.widgets.sort_by(|a, b| a.2.cmp(b.2));
+    }
+    summaries
+}
+
+/// Short text for all summaries, e.g. "2 contacts open, 5 lights on".
+pub(crate) fn summary_text(summaries: &[NotificationSummary]) -> String {
+    summaries
+        .iter()
+        .map(|s| format!("{} {}", s.num, s.label))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Map the color names used by widgets to colors we can render.
+fn to_color32(color: &str) -> Color32 {
+    match color {
+        "red" => Color32::RED,
+        "yellow" => Color32::YELLOW,
+        "green" => Color32::GREEN,
+        _ => Color32::GRAY,
+    }
+}
+
+/// Render a badge with the number of notifications, in the color of the most important one.
+fn render_badge(ui: &mut egui::Ui, text: &str, summaries: &[NotificationSummary]) {
+    let num: u32 = summaries.iter().map(|s| s.num).sum();
+    let color = summaries
+        .first()
+        .map(|s| to_color32(s.color))
+        .unwrap_or(Color32::GRAY);
+
+    ui.label(text);
+    ui.label(
+        RichText::new(format!(" {} ", num))
+            .strong()
+            .color(Color32::BLACK)
+            .background_color(color),
+    )
+    .on_hover_text(summary_text(summaries));
+}
+
+/// Render the notification center.
+///
+/// Shows a badge for each view and room as well as a list of all notifications. Clicking a
+/// widget in that list moves the camera to it.
+pub(crate) fn render_notifications(
+    mut contexts: EguiContexts,
+    mut device_model: ResMut<DeviceModel>,
+    mut cameras: Query<&mut LookTransform>,
+) {
+    let notifications = device_model.notifications();
+    let mut fly_to = None;
+
+    egui::Window::new("Notifications")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let summaries = summarize(notifications.iter());
+            ui.label(summary_text(&summaries));
+            ui.separator();
+
+            let mut views = BTreeMap::new();
+            let mut rooms = BTreeMap::new();
+            for notification in &notifications {
+                views
+                    .entry(&notification.view_name)
+                    .or_insert(vec![])
+                    .push(notification);
+                if let Some(room) = &notification.room {
+                    rooms.entry(room).or_insert(vec![]).push(notification);
+                }
+            }
+
+            ui.horizontal_wrapped(|ui| {
+                for (view_name, notifications) in views {
+                    render_badge(ui, view_name, &summarize(notifications.into_iter()));
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                for (room, notifications) in rooms {
+                    render_badge(ui, room, &summarize(notifications.into_iter()));
+                }
+            });
+            ui.separator();
+
+            for summary in &summaries {
+                let header = RichText::new(format!("{} {}", summary.num, summary.label))
+                    .color(to_color32(summary.color));
+                ui.collapsing(header, |ui| {
+                    for (view_name, widget_name, label) in &summary.widgets {
+                        if ui.button(*label).clicked() {
+                            fly_to = Some((view_name.to_string(), widget_name.to_string()));
+                        }
+                    }
+                });
+            }
+        });
+
+    if let Some((view_name, widget_name)) = fly_to {
+        device_model.current_view = Some(view_name);
+        if let Some(position) = device_model.widget_position(&widget_name) {
+            // Keep the angle and distance of the camera, the camera controller smoothly moves
+            // the camera to the new position.
+            for mut look_transform in &mut cameras {
+                let offset = look_transform.eye - look_transform.target;
+                look_transform.target = position;
+                look_transform.eye = position + offset;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_summarize_notifications() {
+    let notification = |widget_name: &str, color: &str, priority: u8, label: &str| Notification {
+        view_name: "floorplan".to_string(),
+        widget_name: widget_name.to_string(),
+        label: widget_name.to_string(),
+        room: None,
+        status: NotificationStatus {
+            color: color.to_string(),
+            priority,
+            num: 1,
+            label: label.to_string(),
+        },
+    };
+    let notifications = vec![
+        notification("kitchen", "yellow", 1, "lights on"),
+        notification("window", "red", 2, "contacts open"),
+        notification("living", "yellow", 1, "lights on"),
+        notification("door", "red", 2, "contacts open"),
+        notification("bath", "yellow", 1, "lights on"),
+    ];
+
+    let summaries = summarize(notifications.iter());
+    assert_eq!(summary_text(&summaries), "2 contacts open, 3 lights on");
+    assert_eq!(summaries[0].color, "red");
+    assert_eq!(
+        summaries[0]
+            .widgets
+            .iter()
+            .map(|w| w.2)
+            .collect::<Vec<&str>>(),
+        vec!["door", "window"]
+    );
+}
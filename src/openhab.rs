@@ -1,5 +1,7 @@
+use bevy::prelude::Event;
 use hsl::HSL;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// State change requested from widget.
 ///
@@ -9,6 +11,16 @@ pub enum WidgetInteraction {
     FullscreenRequest(bool),
 }
 
+impl WidgetInteraction {
+    /// Request changing the state of the item with the given key to the given value.
+    pub fn state_change(key: &str, value: &str) -> Self {
+        WidgetInteraction::StateChange(RequestedStateChangeFromWidget {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct RequestedStateChangeFromWidget {
     pub key: String,
@@ -18,15 +30,19 @@ pub struct RequestedStateChangeFromWidget {
 /// State change request to be appended for sending over the network.
 ///
 /// This has the key looked up to an item in the backend.
+#[derive(Event, Debug, Clone)]
 pub struct RequestedStateChange {
     pub item: String,
     pub value: String,
+    /// Widget that requested the change, for reporting errors.
+    pub widget: String,
 }
 
 use crate::{errors::DeviceModelError, widget_settings::WidgetItemList};
 
 impl RequestedStateChange {
     pub(crate) fn from_widget_request(
+        widget_name: &str,
         widget_request: &RequestedStateChangeFromWidget,
         item_list: &WidgetItemList,
     ) -> Result<Self, DeviceModelError> {
@@ -35,6 +51,7 @@ impl RequestedStateChange {
                 return Ok(Self {
                     item: item.to_string(),
                     value: widget_request.value.clone(),
+                    widget: widget_name.to_string(),
                 });
             }
         }
@@ -64,6 +81,84 @@ pub struct OpenHabState {
     pub ohtype: Option<String>,
 }
 
+/// A single value as returned by the backend's persistence service.
+#[derive(Deserialize, Debug)]
+pub struct OpenHabPersistenceDatapoint {
+    /// Milliseconds since the epoch.
+    pub time: i64,
+    pub state: String,
+}
+
+/// History of an item as returned by `/rest/persistence/items/{item}`.
+#[derive(Deserialize, Debug)]
+pub struct OpenHabPersistenceResponse {
+    pub name: String,
+    pub data: Vec<OpenHabPersistenceDatapoint>,
+}
+
+/// Parse the history of an item returned by the backend's persistence service.
+///
+/// Returns the item's name and its datapoints as pairs of timestamp (seconds since the epoch)
+/// and value. States that are not numbers are skipped.
+pub(crate) fn parse_persistence_response(
+    payload: &[u8],
+) -> Result<(String, Vec<[f64; 2]>), DeviceModelError> {
+    let response: OpenHabPersistenceResponse = serde_json::from_slice(payload).map_err(|e| {
+        DeviceModelError::ParserError(format!("Failed to parse persistence response: {:?}", e))
+    })?;
+
+    let datapoints = response
+        .data
+        .iter()
+        .filter_map(|datapoint| {
+            crate::history::parse_value(&datapoint.state)
+                .map(|value| [datapoint.time as f64 / 1000., value])
+        })
+        .collect();
+
+    Ok((response.name, datapoints))
+}
+
+#[test]
+fn test_parse_persistence_response() {
+    let payload = r#"{"name":"OutdoorTemperature","datapoints":"2","data":[
+        {"time":1712345678000,"state":"21.5 °C"},
+        {"time":1712345679000,"state":"NULL"}]}"#;
+    assert_eq!(
+        parse_persistence_response(payload.as_bytes()),
+        Ok(("OutdoorTemperature".to_string(), vec![[1712345678., 21.5]]))
+    );
+}
+
+/// An item as returned by `/rest/items?metadata=semantics`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenHabItem {
+    pub name: String,
+    /// Type of the item, e.g. `Switch` or `Number:Temperature`.
+    #[serde(rename = "type")]
+    pub ohtype: String,
+    pub label: Option<String>,
+    #[serde(default)]
+    pub metadata: OpenHabItemMetadata,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct OpenHabItemMetadata {
+    pub semantics: Option<OpenHabSemantics>,
+}
+
+/// Place of an item in the semantic model.
+#[derive(Deserialize, Debug)]
+pub struct OpenHabSemantics {
+    /// Semantic class, e.g. `Location_Indoor_Room_Kitchen`, `Equipment_Lightbulb` or
+    /// `Point_Measurement`.
+    pub value: String,
+    /// Relations to other items, e.g. `hasLocation`, `isPointOf` or `relatesTo`.
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+}
+
 pub fn openhab_hsb_to_rgb(hsb: [f32; 3]) -> [f32; 3] {
     let rgb = HSL {
         h: hsb[0] as f64,
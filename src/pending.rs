@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::history::parse_value;
+use crate::widget_settings::ItemName;
+
+/// Seconds to wait for the backend to confirm a requested state change.
+const CONFIRM_TIMEOUT: f64 = 5.;
+
+/// A state change sent to the backend, but not yet confirmed by it.
+#[derive(Debug)]
+struct PendingChange {
+    requested: String,
+    /// Time the change was requested or will be retried, as seconds since the epoch.
+    sent: f64,
+}
+
+/// Tracks state changes that widgets already show, until the backend confirms them.
+///
+/// While a change is pending, other updates of the item are only remembered, so the widget keeps
+/// showing the requested state. If the change fails or isn't confirmed in time, the last state
+/// reported by the backend is restored.
+#[derive(Debug, Default)]
+pub(crate) struct PendingChanges {
+    changes: HashMap<ItemName, PendingChange>,
+    /// Last state reported by the backend for each item.
+    states: HashMap<ItemName, String>,
+}
+
+/// Whether the backend's state matches the requested one, e.g. "100" and "100.0".
+fn is_same_state(requested: &str, state: &str) -> bool {
+    match (parse_value(requested), parse_value(state)) {
+        (Some(requested), Some(state)) => (requested - state).abs() < f64::EPSILON,
+        _ => requested == state,
+    }
+}
+
+impl PendingChanges {
+    pub(crate) fn requested(&mut self, item_name: &str, value: &str, now: f64) {
+        self.changes.insert(
+            item_name.to_string(),
+            PendingChange {
+                requested: value.to_string(),
+                sent: now,
+            },
+        );
+    }
+
+    /// The change of an item is sent again at the given time, wait for the backend from then on.
+    pub(crate) fn resent(&mut self, item_name: &str, at: f64) {
+        if let Some(change) = self.changes.get_mut(item_name) {
+            change.sent = at;
+        }
+    }
+
+    /// Last state reported by the backend for the given item.
+    pub(crate) fn last_state(&self, item_name: &str) -> Option<&str> {
+        self.states.get(item_name).map(|s| s.as_str())
+    }
+
+    pub(crate) fn is_pending(&self, item_name: &str) -> bool {
+        self.changes.contains_key(item_name)
+    }
+
+    /// Remember a state reported by the backend.
+    ///
+    /// Returns whether the state should be passed to the widgets, which is the case unless it
+    /// differs from a pending change. A matching state confirms the change.
+    pub(crate) fn state_received(&mut self, item_name: &str, state: &str) -> bool {
+        self.states.insert(item_name.to_string(), state.to_string());
+        match self.changes.get(item_name) {
+            Some(change) if is_same_state(&change.requested, state) => {
+                self.changes.remove(item_name);
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    /// Drop the pending change of an item, returning the state to roll back to.
+    pub(crate) fn failed(&mut self, item_name: &str) -> Option<Option<String>> {
+        self.changes.remove(item_name)?;
+        Some(self.states.get(item_name).cloned())
+    }
+
+    /// Drop changes not confirmed in time, returning the items and the states to roll back to.
+    pub(crate) fn take_expired(&mut self, now: f64) -> Vec<(ItemName, Option<String>)> {
+        let expired = self
+            .changes
+            .iter()
+            .filter(|(_, change)| now - change.sent > CONFIRM_TIMEOUT)
+            .map(|(item_name, _)| item_name.to_string())
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .filter_map(|item_name| {
+                let state = self.failed(&item_name)?;
+                Some((item_name, state))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_pending_changes() {
+    let mut pending = PendingChanges::default();
+    assert!(pending.state_received("Light", "0"));
+
+    // Other states are held back until the requested one is confirmed
+    pending.requested("Light", "100", 0.);
+    assert!(!pending.state_received("Light", "50"));
+    assert!(pending.state_received("Light", "100.0"));
+    assert!(!pending.is_pending("Light"));
+
+    // Roll back to the last state reported by the backend
+    pending.requested("Light", "0", 10.);
+    assert_eq!(pending.take_expired(12.), vec![]);
+    assert_eq!(
+        pending.take_expired(20.),
+        vec![("Light".to_string(), Some("100.0".to_string()))]
+    );
+    // Retried changes are kept until the retry timed out
+    pending.requested("Light", "50", 30.);
+    pending.resent("Light", 40.);
+    assert_eq!(pending.take_expired(42.), vec![]);
+    assert_eq!(pending.take_expired(46.).len(), 1);
+
+    pending.requested("Switch", "ON", 20.);
+    assert_eq!(pending.failed("Switch"), Some(None));
+    assert_eq!(pending.failed("Switch"), None);
+}
@@ -0,0 +1,316 @@
+use bevy::{prelude::*, render::primitives::Aabb, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+
+use crate::device_model::{DeviceModel, SceneInteraction};
+use crate::openhab::RequestedStateChange;
+use crate::widget_settings::WidgetName;
+
+/// Seconds the mouse button has to be held down on an entity to open the fullscreen view.
+const LONG_PRESS_DURATION: f32 = 0.6;
+
+/// Distance in pixels the cursor may move while pressed before we consider it a camera drag.
+const DRAG_THRESHOLD: f32 = 5.;
+
+/// Lights do not have a mesh, so we pick them within this radius around their position.
+const LIGHT_PICK_RADIUS: f32 = 0.3;
+
+const HIGHLIGHT_COLOR: Color = Color::rgb(0.3, 0.3, 0.1);
+
+/// A press on a scene entity that has not been released yet.
+struct Press {
+    widget_name: WidgetName,
+    cursor_position: Vec2,
+    started: f32,
+    long_press_sent: bool,
+}
+
+#[derive(Default, Resource)]
+pub(crate) struct PickingState {
+    /// Mesh entity currently under the cursor together with the widget owning it.
+    hovered: Option<(Entity, WidgetName)>,
+    press: Option<Press>,
+}
+
+/// Marks a mesh that is highlighted because it is hovered.
+///
+/// Materials are shared between meshes of the glTF scene, so highlighted meshes get their own
+/// copy of the material. The original one is restored once the mesh is no longer hovered.
+#[derive(Component)]
+pub(crate) struct Highlighted {
+    pub(crate) original: Handle<StandardMaterial>,
+}
+
+/// Distance along the ray to the first intersection with the given axis aligned box, if any.
+pub(crate) fn ray_aabb_intersection(
+    origin: Vec3,
+    direction: Vec3,
+    min: Vec3,
+    max: Vec3,
+) -> Option<f32> {
+    let inverse_direction = direction.recip();
+    let t1 = (min - origin) * inverse_direction;
+    let t2 = (max - origin) * inverse_direction;
+    let t_near = t1.min(t2).max_element();
+    let t_far = t1.max(t2).min_element();
+
+    if t_near <= t_far && t_far >= 0. {
+        Some(t_near.max(0.))
+    } else {
+        None
+    }
+}
+
+/// Distance along the ray to the point closest to the given sphere center, if the ray hits it.
+pub(crate) fn ray_sphere_intersection(
+    origin: Vec3,
+    direction: Vec3,
+    center: Vec3,
+    radius: f32,
+) -> Option<f32> {
+    let t = (center - origin).dot(direction);
+    if t < 0. {
+        return None;
+    }
+    if (origin + direction * t).distance(center) <= radius {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Distance along the ray to its intersection with the given triangle, if any.
+pub(crate) fn ray_triangle_intersection(
+    origin: Vec3,
+    direction: Vec3,
+    [a, b, c]: [Vec3; 3],
+) -> Option<f32> {
+    // Möller–Trumbore, with u and v the barycentric coordinates of the intersection
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        // Parallel to the triangle
+        return None;
+    }
+    let inverse_determinant = 1. / determinant;
+    let a_to_origin = origin - a;
+    let u = a_to_origin.dot(p) * inverse_determinant;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = a_to_origin.cross(ab);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = ac.dot(q) * inverse_determinant;
+    (t >= 0.).then_some(t)
+}
+
+#[test]
+fn test_ray_intersections() {
+    let min = Vec3::new(-1., -1., -1.);
+    let max = Vec3::new(1., 1., 1.);
+    assert_eq!(
+        ray_aabb_intersection(Vec3::new(0., 5., 0.), Vec3::NEG_Y, min, max),
+        Some(4.)
+    );
+    assert_eq!(
+        ray_aabb_intersection(Vec3::new(3., 5., 0.), Vec3::NEG_Y, min, max),
+        None
+    );
+    assert_eq!(
+        ray_aabb_intersection(Vec3::new(0., 5., 0.), Vec3::Y, min, max),
+        None
+    );
+    assert_eq!(
+        ray_sphere_intersection(Vec3::new(0.1, 5., 0.), Vec3::NEG_Y, Vec3::ZERO, 0.3),
+        Some(5.)
+    );
+    assert_eq!(
+        ray_sphere_intersection(Vec3::new(1., 5., 0.), Vec3::NEG_Y, Vec3::ZERO, 0.3),
+        None
+    );
+    let triangle = [Vec3::ZERO, Vec3::X, Vec3::Z];
+    assert_eq!(
+        ray_triangle_intersection(Vec3::new(0.2, 5., 0.2), Vec3::NEG_Y, triangle),
+        Some(5.)
+    );
+    assert_eq!(
+        ray_triangle_intersection(Vec3::new(0.8, 5., 0.8), Vec3::NEG_Y, triangle),
+        None
+    );
+}
+
+/// Find the widget owning the given entity.
+///
+/// Blender items usually name a node of the glTF scene, while the meshes are children of that
+/// node. So we walk up the hierarchy until we find a name a widget is configured for.
+fn find_widget(
+    device_model: &DeviceModel,
+    entity: Entity,
+    names: &Query<&Name>,
+    parents: &Query<&Parent>,
+) -> Option<WidgetName> {
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        if let Ok(name) = names.get(entity) {
+            if let Some(widget_name) = device_model.widget_for_entity(name.as_str()) {
+                return Some(widget_name.to_string());
+            }
+        }
+        current = parents.get(entity).ok().map(|parent| parent.get());
+    }
+    None
+}
+
+/// Raycast from the cursor into the scene and forward clicks and long presses on entities to the
+/// widget configured for them in `blender_items`.
+///
+/// Only entities belonging to a widget are considered. Otherwise, large meshes such as floor and
+/// walls would hide everything inside of them.
+pub(crate) fn pick_scene_objects(
+    mut picking_state: ResMut<PickingState>,
+    mut device_model: ResMut<DeviceModel>,
+    mut contexts: EguiContexts,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    meshes: Query<(Entity, &Aabb, &GlobalTransform), With<Handle<Mesh>>>,
+    lights: Query<(Entity, &GlobalTransform), Or<(With<PointLight>, With<SpotLight>)>>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    mut ev_state_change: EventWriter<RequestedStateChange>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let (camera, camera_global_transform) = camera.single();
+
+    // Egui widgets are rendered on top of the scene, they get the input first.
+    let cursor_position = match window.cursor_position() {
+        Some(position) if !contexts.ctx_mut().is_pointer_over_area() => position,
+        _ => {
+            picking_state.hovered = None;
+            picking_state.press = None;
+            return;
+        }
+    };
+
+    let Some(ray) = camera.viewport_to_world(camera_global_transform, cursor_position) else {
+        return;
+    };
+    let direction = *ray.direction;
+
+    // Nearest hit of an entity that belongs to a widget
+    let mut nearest: Option<(f32, Entity, WidgetName, bool)> = None;
+    for (entity, aabb, transform) in &meshes {
+        let world_to_local = transform.compute_matrix().inverse();
+        let distance = ray_aabb_intersection(
+            world_to_local.transform_point3(ray.origin),
+            world_to_local.transform_vector3(direction),
+            aabb.min().into(),
+            aabb.max().into(),
+        );
+        if let Some(distance) = distance {
+            if nearest.as_ref().map_or(true, |n| distance < n.0) {
+                if let Some(widget_name) = find_widget(&device_model, entity, &names, &parents) {
+                    nearest = Some((distance, entity, widget_name, true));
+                }
+            }
+        }
+    }
+    for (entity, transform) in &lights {
+        let distance = ray_sphere_intersection(
+            ray.origin,
+            direction,
+            transform.translation(),
+            LIGHT_PICK_RADIUS,
+        );
+        if let Some(distance) = distance {
+            if nearest.as_ref().map_or(true, |n| distance < n.0) {
+                if let Some(widget_name) = find_widget(&device_model, entity, &names, &parents) {
+                    nearest = Some((distance, entity, widget_name, false));
+                }
+            }
+        }
+    }
+
+    // Only meshes can be highlighted
+    picking_state.hovered = nearest
+        .as_ref()
+        .filter(|(_, _, _, is_mesh)| *is_mesh)
+        .map(|(_, entity, widget_name, _)| (*entity, widget_name.to_string()));
+    let picked_widget = nearest.map(|(_, _, widget_name, _)| widget_name);
+
+    let mut interaction = None;
+    if mouse.just_pressed(MouseButton::Left) {
+        picking_state.press = picked_widget.map(|widget_name| Press {
+            widget_name,
+            cursor_position,
+            started: time.elapsed_seconds(),
+            long_press_sent: false,
+        });
+    } else if let Some(press) = &mut picking_state.press {
+        if press.cursor_position.distance(cursor_position) > DRAG_THRESHOLD {
+            // The camera is being moved, this is not meant to be a click
+            picking_state.press = None;
+        } else if mouse.just_released(MouseButton::Left) {
+            if !press.long_press_sent {
+                interaction = Some((press.widget_name.to_string(), SceneInteraction::Click));
+            }
+            picking_state.press = None;
+        } else if !press.long_press_sent
+            && time.elapsed_seconds() - press.started > LONG_PRESS_DURATION
+        {
+            press.long_press_sent = true;
+            interaction = Some((press.widget_name.to_string(), SceneInteraction::LongPress));
+        }
+    }
+
+    if let Some((widget_name, interaction)) = interaction {
+        debug!(
+            "Scene interaction {:?} on widget {}",
+            interaction, widget_name
+        );
+        match device_model.scene_interaction(&widget_name, interaction) {
+            Ok(requested_state_changes) => {
+                ev_state_change.send_batch(requested_state_changes);
+            }
+            Err(e) => error!("Failed to handle scene interaction: {:?}", e),
+        }
+    }
+}
+
+/// Highlight the mesh under the cursor by making it emissive.
+pub(crate) fn highlight_hovered(
+    mut commands: Commands,
+    picking_state: Res<PickingState>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut highlighted: Query<(Entity, &Highlighted, &mut Handle<StandardMaterial>)>,
+    mut candidates: Query<&mut Handle<StandardMaterial>, Without<Highlighted>>,
+) {
+    let hovered = picking_state.hovered.as_ref().map(|(entity, _)| *entity);
+
+    // Restore meshes that are no longer hovered
+    for (entity, highlight, mut material) in &mut highlighted {
+        if Some(entity) != hovered {
+            materials.remove(material.id());
+            *material = highlight.original.clone();
+            commands.entity(entity).remove::<Highlighted>();
+        }
+    }
+
+    if let Some(entity) = hovered {
+        if let Ok(mut material) = candidates.get_mut(entity) {
+            if let Some(mut highlight_material) = materials.get(material.id()).cloned() {
+                highlight_material.emissive = HIGHLIGHT_COLOR;
+                let original = material.clone();
+                *material = materials.add(highlight_material);
+                commands.entity(entity).insert(Highlighted { original });
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::events::{PositionModification, TintModification, VisibilityModification};
+use crate::picking::Highlighted;
+
+/// Marks a mesh whose material is tinted.
+///
+/// Like for highlighting, tinted meshes get their own copy of the material, so other meshes
+/// sharing it are not affected.
+#[derive(Component)]
+pub(crate) struct Tinted {
+    original: Handle<StandardMaterial>,
+    tint: Handle<StandardMaterial>,
+}
+
+/// Marks an entity that has been moved, remembering its original position.
+#[derive(Component)]
+pub(crate) struct Moved {
+    original: Vec3,
+}
+
+/// Whether the entity or its parent has the given name.
+///
+/// Blender items name a node of the glTF scene, while the meshes are children of that node.
+fn has_name(entity: Entity, name: &str, names: &Query<&Name>, parents: &Query<&Parent>) -> bool {
+    let parent = parents.get(entity).ok().map(|parent| parent.get());
+    [Some(entity), parent]
+        .into_iter()
+        .flatten()
+        .any(|entity| names.get(entity).map_or(false, |n| n.as_str() == name))
+}
+
+/// Tint or restore the meshes of entities.
+///
+/// Meshes may be highlighted because they are hovered at the same time. In that case, the tint
+/// becomes the material restored once the mesh is no longer hovered.
+pub(crate) fn apply_tints(
+    mut commands: Commands,
+    mut ev_tint: EventReader<TintModification>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: Query<(
+        Entity,
+        &mut Handle<StandardMaterial>,
+        Option<&Tinted>,
+        Option<&mut Highlighted>,
+    )>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+) {
+    for ev in ev_tint.read() {
+        for (entity, mut material, tinted, highlighted) in &mut meshes {
+            if !has_name(entity, &ev.entity_name, &names, &parents) {
+                continue;
+            }
+            // Material shown once the mesh is no longer hovered
+            let mut current = match highlighted {
+                Some(highlighted) => highlighted.map_unchanged(|h| &mut h.original),
+                None => material.reborrow(),
+            };
+
+            match (ev.color, tinted) {
+                (Some(color), Some(tinted)) => {
+                    if let Some(tint) = materials.get_mut(&tinted.tint) {
+                        tint.emissive = color;
+                    }
+                }
+                (Some(color), None) => {
+                    if let Some(mut tint) = materials.get(current.id()).cloned() {
+                        tint.emissive = color;
+                        let tint = materials.add(tint);
+                        let original = std::mem::replace(&mut *current, tint.clone());
+                        commands.entity(entity).insert(Tinted { original, tint });
+                    }
+                }
+                (None, Some(tinted)) => {
+                    materials.remove(tinted.tint.id());
+                    *current = tinted.original.clone();
+                    commands.entity(entity).remove::<Tinted>();
+                }
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// Move entities to the requested positions, or back to their original ones.
+///
+/// The position is set as translation relative to the entity's parent, which for nodes of the
+/// glTF scene is the scene's origin.
+pub(crate) fn apply_positions(
+    mut commands: Commands,
+    mut ev_position: EventReader<PositionModification>,
+    mut entities: Query<(Entity, &Name, &mut Transform, Option<&Moved>)>,
+) {
+    // Entities moved for the first time, their marker is only added once the system is done
+    let mut moved_now = HashMap::new();
+    for ev in ev_position.read() {
+        for (entity, name, mut transform, moved) in &mut entities {
+            if name.as_str() != ev.entity_name {
+                continue;
+            }
+            let original = moved
+                .map(|moved| moved.original)
+                .or(moved_now.get(&entity).copied());
+            match (ev.position, original) {
+                (Some(position), Some(_)) => transform.translation = position,
+                (Some(position), None) => {
+                    moved_now.insert(entity, transform.translation);
+                    commands.entity(entity).insert(Moved {
+                        original: transform.translation,
+                    });
+                    transform.translation = position;
+                }
+                (None, Some(original)) => transform.translation = original,
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// Show or hide entities.
+pub(crate) fn apply_visibility(
+    mut ev_visibility: EventReader<VisibilityModification>,
+    mut entities: Query<(&Name, &mut Visibility)>,
+) {
+    for ev in ev_visibility.read() {
+        for (name, mut visibility) in &mut entities {
+            if name.as_str() == ev.entity_name {
+                *visibility = match ev.visible {
+                    true => Visibility::Inherited,
+                    false => Visibility::Hidden,
+                };
+            }
+        }
+    }
+}
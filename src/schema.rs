@@ -0,0 +1,84 @@
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde_json::{json, Value};
+
+use crate::config::ItemConfiguration;
+use crate::device_model::ITEM_TYPES;
+use crate::item;
+
+/// Schema of the `templateConf` of the given widget type, `None` if the widget ignores it.
+fn template_conf_schema(generator: &mut SchemaGenerator, item_type: &str) -> Option<Schema> {
+    // The configuration is optional, so `null` is valid as well.
+    fn optional<T: JsonSchema>(generator: &mut SchemaGenerator) -> Option<Schema> {
+        Some(generator.subschema_for::<Option<T>>())
+    }
+
+    match item_type {
+        "Switch" => optional::<item::switch::SwitchConfig>(generator),
+        "Text" => optional::<item::TextConfig>(generator),
+        "Climate" => optional::<item::climate::ClimateConfig>(generator),
+        "Music" => optional::<item::MusicConfig>(generator),
+        "Scene" => optional::<item::SceneConfig>(generator),
+        "Car" => optional::<item::car::CarConfig>(generator),
+        "Robot" => optional::<item::robot::RobotConfig>(generator),
+        "EnergyMonitor" => optional::<item::energy_monitor::EnergyMonitorConfig>(generator),
+        "Laundry" | "Appliance" => optional::<item::laundry::LaundryConfig>(generator),
+        "LightAuto" => optional::<item::light_auto::LightAutoConfig>(generator),
+        _ => None,
+    }
+}
+
+/// JSON Schema of `items.json`, for validation and autocompletion in editors.
+///
+/// The shape of `templateConf` depends on the widget's `itemType`, which is expressed as one
+/// `if`/`then` condition per widget type.
+pub fn config_schema() -> Value {
+    let mut generator = SchemaSettings::draft07().into_generator();
+
+    let conditions = ITEM_TYPES
+        .iter()
+        .filter_map(|item_type| {
+            let template_conf = template_conf_schema(&mut generator, item_type)?;
+            Some(json!({
+                "if": { "properties": { "itemType": { "const": item_type } } },
+                "then": { "properties": { "templateConf": template_conf } },
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    let mut schema = json!(generator.into_root_schema_for::<ItemConfiguration>());
+    let item = &mut schema["definitions"]["Item"];
+    item["properties"]["itemType"] = json!({ "type": "string", "enum": ITEM_TYPES });
+    item["allOf"] = json!(conditions);
+    schema
+}
+
+#[test]
+fn test_config_schema() {
+    // Every widget type in the schema can be instantiated
+    for item_type in ITEM_TYPES {
+        assert!(
+            crate::device_model::DeviceModel::init_widget(item_type, &Default::default()).is_some(),
+            "{}",
+            item_type
+        );
+    }
+
+    let schema = config_schema();
+    let item = &schema["definitions"]["Item"];
+    assert_eq!(item["properties"]["itemType"]["enum"][1], "Switch");
+    assert_eq!(
+        item["allOf"][0],
+        json!({
+            "if": { "properties": { "itemType": { "const": "Switch" } } },
+            "then": { "properties": { "templateConf": {
+                "anyOf": [{ "$ref": "#/definitions/SwitchConfig" }, { "type": "null" }]
+            } } },
+        })
+    );
+    assert_eq!(
+        schema["definitions"]["SwitchConfig"]["required"],
+        json!(["icon"])
+    );
+}
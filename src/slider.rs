@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, RichText, Sense},
+    EguiContexts,
+};
+use serde_json::{Map, Value};
+
+use crate::device_model::DeviceModel;
+
+/// Width of a single card in the slider strip.
+const CARD_WIDTH: f32 = 180.;
+
+/// Get a value of the widget's state as text, if it is set.
+fn get(state: &Map<String, Value>, key: &str) -> Option<String> {
+    match state.get(key)? {
+        Value::String(s) => Some(s.to_string()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+/// Render the contents of the card for the given slider template.
+fn render_card(ui: &mut egui::Ui, label: &str, template: &str, state: &Map<String, Value>) {
+    let since = get(state, "__last_updated");
+    match template {
+        "slider-door" => {
+            ui.label(RichText::new(format!("🚪 {} open", label)).strong());
+            if let Some(since) = since {
+                ui.label(RichText::new(since).small());
+            }
+        }
+        "slider-music" => {
+            ui.label(
+                RichText::new(format!("🎵 {}", get(state, "title").unwrap_or_default())).strong(),
+            );
+            if let Some(artist) = get(state, "artist") {
+                ui.label(artist);
+            }
+            if let Some(zone) = get(state, "zonename") {
+                ui.label(RichText::new(zone).small());
+            }
+        }
+        "slider-car" => {
+            ui.label(RichText::new(format!("🚗 {}", label)).strong());
+            if let Some(soc) = get(state, "soc") {
+                let range = get(state, "range").unwrap_or_default();
+                ui.label(format!("{} % {}", soc, range));
+            }
+            if state.get("is_charging") == Some(&Value::Bool(true)) {
+                ui.label(format!(
+                    "⚡ {}",
+                    get(state, "charging_power").unwrap_or_default()
+                ));
+            } else if state.get("is_plugged") == Some(&Value::Bool(true)) {
+                ui.label("🔌 plugged in");
+            }
+            if let Some(seen) = get(state, "eventstamp") {
+                ui.label(RichText::new(seen).small());
+            }
+        }
+        "slider-robot" => {
+            ui.label(RichText::new(format!("🧹 {}", label)).strong());
+            if let Some(phase) = get(state, "phase") {
+                ui.label(phase);
+            }
+        }
+        "slider-laundry" => {
+            let phase = get(state, "phase").unwrap_or("running".to_string());
+            ui.label(RichText::new(format!("🧺 {} {}", label, phase)).strong());
+            if let (Some(remaining), Some(finish_time)) =
+                (get(state, "remaining"), get(state, "finish_time"))
+            {
+                ui.label(format!("{} left, done at {}", remaining, finish_time));
+            }
+            if let Some(since) = since {
+                ui.label(RichText::new(format!("started {}", since)).small());
+            }
+        }
+        "slider-energy-monitor" => {
+            ui.label(
+                RichText::new(format!(
+                    "⚡ {} W",
+                    get(state, "leistung").unwrap_or("n.a.".to_string())
+                ))
+                .strong(),
+            );
+            if let Some(Value::Array(highest)) = state.get("power_meter_highest") {
+                for entry in highest {
+                    if let Some([meter, value]) = entry.as_array().map(|e| e.as_slice()) {
+                        let meter = meter.as_str().unwrap_or_default();
+                        let value = value.as_str().unwrap_or_default();
+                        ui.label(RichText::new(format!("{}: {}", meter, value)).small());
+                    }
+                }
+            }
+        }
+        _ => {
+            ui.label(RichText::new(label).strong());
+            ui.label(RichText::new(template).small());
+        }
+    }
+}
+
+/// Render a strip at the bottom of the screen with a card for each widget that wants to show a
+/// slider, ordered by priority.
+///
+/// Clicking a card opens the fullscreen view of the widget.
+pub(crate) fn render_slider_strip(
+    mut contexts: EguiContexts,
+    mut device_model: ResMut<DeviceModel>,
+) {
+    let sliders = device_model.active_sliders();
+    if sliders.is_empty() {
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("sliders").show(contexts.ctx_mut(), |ui| {
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (widget_name, label, template, state) in &sliders {
+                    let response = egui::Frame::group(ui.style())
+                        .show(ui, |ui| {
+                            ui.set_width(CARD_WIDTH);
+                            ui.vertical(|ui| render_card(ui, label, template, state));
+                        })
+                        .response
+                        .interact(Sense::click());
+                    if response.clicked() {
+                        device_model.fullscreen_widget = Some(widget_name.to_string());
+                    }
+                }
+            });
+        });
+    });
+}
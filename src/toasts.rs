@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContexts,
+};
+
+use crate::history;
+
+/// Seconds each toast is shown.
+const TOAST_DURATION: f64 = 8.;
+/// Older toasts are dropped once there are more.
+const MAX_TOASTS: usize = 5;
+
+struct Toast {
+    text: String,
+    /// Time until the toast is shown, as seconds since the epoch.
+    until: f64,
+}
+
+/// Short-lived messages for the user, e.g. about commands the backend rejected.
+#[derive(Default, Resource)]
+pub(crate) struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub(crate) fn error(&mut self, text: &str) {
+        self.toasts.push(Toast {
+            text: text.to_string(),
+            until: history::now() + TOAST_DURATION,
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+}
+
+/// Render the current toasts in the bottom right corner, above the sliders.
+///
+/// Clicking a toast dismisses it.
+pub(crate) fn render_toasts(mut contexts: EguiContexts, mut toasts: ResMut<Toasts>) {
+    let now = history::now();
+    toasts.toasts.retain(|toast| toast.until > now);
+    if toasts.toasts.is_empty() {
+        return;
+    }
+
+    let mut dismissed = None;
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10., -100.])
+        .show(contexts.ctx_mut(), |ui| {
+            for (i, toast) in toasts.toasts.iter().enumerate() {
+                let response = egui::Frame::popup(ui.style())
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(&toast.text).color(Color32::RED));
+                    })
+                    .response
+                    .interact(egui::Sense::click());
+                if response.clicked() {
+                    dismissed = Some(i);
+                }
+            }
+        });
+    if let Some(i) = dismissed {
+        toasts.toasts.remove(i);
+    }
+}
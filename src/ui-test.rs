@@ -19,7 +19,7 @@ use bevy_egui::{
     egui::{self, Color32},
     EguiContexts, EguiPlugin,
 };
-use emoji::get_emoji;
+use emoji::{get_emoji, light_bulb};
 use widget_settings::WidgetRenderSetting;
 
 fn main() {
@@ -49,7 +49,8 @@ fn ui_example_system(mut contexts: EguiContexts) {
         (50., 50.),
         true,
         Some(1234.),
-        get_emoji("E150"),
+        None,
+        get_emoji("E150").unwrap(),
         ui::DARK_GREEN,
         None,
         None,
@@ -66,7 +67,8 @@ fn ui_example_system(mut contexts: EguiContexts) {
         (50., 200.),
         true,
         None,
-        get_emoji("1F4A1"),
+        None,
+        light_bulb(),
         ui::DARK_YELLOW,
         None,
         None,
@@ -83,7 +85,8 @@ fn ui_example_system(mut contexts: EguiContexts) {
         (100., 50.),
         false,
         None,
-        get_emoji("1F4A1"),
+        None,
+        light_bulb(),
         ui::DARK_YELLOW,
         None,
         None,
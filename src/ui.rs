@@ -5,6 +5,7 @@ use bevy_egui::egui::{
 };
 
 use crate::{
+    item::ChangeStatus,
     openhab::{RequestedStateChangeFromWidget, WidgetInteraction},
     widget_settings::WidgetRenderSetting,
 };
@@ -24,6 +25,7 @@ pub fn render_simple_button(
     render_position: (f32, f32),
     state: bool,
     watts: Option<f32>,
+    status: Option<ChangeStatus>,
     icon: Image,
     on_color: Color32,
     on_command: Option<&str>,
@@ -82,6 +84,17 @@ pub fn render_simple_button(
                         ui.label(RichText::new(format!("{}W", watts)).small());
                     }
                 }
+
+                match status {
+                    Some(ChangeStatus::Pending) => {
+                        ui.spinner();
+                    }
+                    Some(ChangeStatus::Failed) => {
+                        ui.label(RichText::new("⚠").color(Color32::RED))
+                            .on_hover_text("Changing the state failed");
+                    }
+                    Some(ChangeStatus::Confirmed) | None => {}
+                }
             });
 
             // This response contains the actual size of the horizontal().
@@ -129,3 +142,91 @@ pub fn render_simple_button(
 
     request
 }
+
+/// Render the fullscreen window of a widget.
+///
+/// The window has a close button, all other contents are added by the given closure, which
+/// returns the interactions triggered by the user.
+pub fn render_fullscreen_window(
+    ctx: &mut egui::Context,
+    render_setting: &WidgetRenderSetting,
+    add_contents: impl FnOnce(&mut egui::Ui) -> Vec<WidgetInteraction>,
+) -> Vec<WidgetInteraction> {
+    let mut requests = vec![];
+    let title = render_setting
+        .label
+        .as_ref()
+        .unwrap_or(&render_setting.widget_name);
+
+    egui::Window::new(title)
+        .id(render_setting.id.with("fullscreen"))
+        .collapsible(false)
+        .show(ctx, |ui| {
+            requests.append(&mut add_contents(ui));
+            ui.separator();
+            if ui.add(egui::Button::new("Close")).clicked() {
+                requests.push(WidgetInteraction::FullscreenRequest(false));
+            }
+        });
+
+    requests
+}
+
+/// Render a slider for a value of the backend.
+///
+/// While the slider is dragged, the value is kept in egui's memory, so we don't flood the backend
+/// with requests. Returns the new value once the user is done changing it.
+pub fn render_state_slider(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    current: f32,
+    range: std::ops::RangeInclusive<f32>,
+    text: &str,
+) -> Option<f32> {
+    let mut value = ui.data(|d| d.get_temp::<f32>(id)).unwrap_or(current);
+    let response = ui.add(egui::Slider::new(&mut value, range).text(text));
+
+    if response.dragged() {
+        ui.data_mut(|d| d.insert_temp(id, value));
+        None
+    } else {
+        ui.data_mut(|d| d.remove::<f32>(id));
+        if response.drag_stopped() || response.changed() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Render a small card anchored at the widget's position in the scene.
+///
+/// The card shows the widget's label as title and the contents added by the given closure.
+/// A secondary click opens the fullscreen view of the widget.
+pub fn render_info_card(
+    ctx: &mut egui::Context,
+    render_setting: &WidgetRenderSetting,
+    render_position: (f32, f32),
+    add_contents: impl FnOnce(&mut egui::Ui),
+) -> Vec<WidgetInteraction> {
+    let mut requests = vec![];
+    egui::Area::new(render_setting.id)
+        .fixed_pos(egui::pos2(render_position.0, render_position.1))
+        .show(ctx, |ui| {
+            let response = egui::Frame::popup(ui.style())
+                .rounding(ROUNDING)
+                .inner_margin(MARGIN)
+                .show(ui, |ui| {
+                    if let Some(label) = &render_setting.label {
+                        ui.label(RichText::new(label).small());
+                    }
+                    add_contents(ui);
+                })
+                .response
+                .interact(egui::Sense::click());
+            if response.secondary_clicked() {
+                requests.push(WidgetInteraction::FullscreenRequest(true));
+            }
+        });
+    requests
+}
@@ -73,3 +73,72 @@ pub fn test_scale_value() {
         3.6345341
     );
 }
+
+/// Format a state using a pattern like openHAB's state description, e.g. `%.1f °C`.
+///
+/// Supports `%s` for the whole state, `%d` and `%.<precision>f` for its number, `%unit%` for the
+/// unit following the number and `%%`. Numeric placeholders fall back to the whole state if it
+/// doesn't start with a number.
+pub fn format_state(pattern: &str, state: &str) -> String {
+    let (number, unit) = match state.split_once(' ') {
+        Some((number, unit)) => (number.parse::<f64>().ok(), unit),
+        None => (state.parse::<f64>().ok(), ""),
+    };
+
+    let mut result = String::new();
+    let mut rest = pattern;
+    while let Some(position) = rest.find('%') {
+        result.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        if let Some(remainder) = rest.strip_prefix("unit%") {
+            result.push_str(unit);
+            rest = remainder;
+        } else if let Some(remainder) = rest.strip_prefix('%') {
+            result.push('%');
+            rest = remainder;
+        } else if let Some(remainder) = rest.strip_prefix('s') {
+            result.push_str(state);
+            rest = remainder;
+        } else if let Some(remainder) = rest.strip_prefix('d') {
+            match number {
+                Some(number) => result.push_str(&format!("{:.0}", number)),
+                None => result.push_str(state),
+            }
+            rest = remainder;
+        } else {
+            // %f or %.<precision>f
+            let precision_end = rest.find('f').unwrap_or(rest.len());
+            let precision = match &rest[..precision_end] {
+                "" => Some(6),
+                specifier => specifier
+                    .strip_prefix('.')
+                    .and_then(|p| p.parse::<usize>().ok()),
+            };
+            match (precision, number) {
+                (Some(precision), Some(number)) if precision_end < rest.len() => {
+                    result.push_str(&format!("{:.*}", precision, number));
+                    rest = &rest[precision_end + 1..];
+                }
+                (Some(_), None) if precision_end < rest.len() => {
+                    result.push_str(state);
+                    rest = &rest[precision_end + 1..];
+                }
+                // Unknown placeholder, keep as is
+                _ => result.push('%'),
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[test]
+pub fn test_format_state() {
+    assert_eq!(format_state("%.1f °C", "21.456"), "21.5 °C");
+    assert_eq!(format_state("%.1f %unit%", "21.456 °C"), "21.5 °C");
+    assert_eq!(format_state("%d %%", "55.6"), "56 %");
+    assert_eq!(format_state("Door: %s", "OPEN"), "Door: OPEN");
+    assert_eq!(format_state("%.2f kWh", "NULL"), "NULL kWh");
+    assert_eq!(format_state("%x", "1"), "%x");
+}
@@ -1,6 +1,16 @@
+use bevy::math::Vec3;
 use bevy::utils::HashMap;
 use bevy_egui::egui;
 
+use crate::errors::{ConfigError, ConfigErrorKind};
+use crate::events::{
+    EnergyFlowModification, LightModification, PositionModification, SceneModificationEvent,
+    TintModification, VisibilityModification,
+};
+
+/// Height at which widgets are placed in the 3D scene.
+pub(crate) const WIDGET_HEIGHT: f32 = 2.0;
+
 #[derive(Debug)]
 pub struct WidgetRenderSetting {
     /// Top coordinate for rendering on the screen.
@@ -15,36 +25,64 @@ pub struct WidgetRenderSetting {
     pub(crate) widget_name: String,
 }
 
+/// Parse a coordinate given as percentage, e.g. "42%".
+fn parse_coordinate(coordinate: &str) -> Result<f32, ConfigErrorKind> {
+    coordinate
+        .replace("%", "")
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| ConfigErrorKind::InvalidCoordinate(coordinate.to_string()))
+}
+
 impl WidgetRenderSetting {
     /// Initialize a render setting instance from the widget configuration
+    ///
+    /// Returns `None` if the widget is not placed on the screen, or the errors of the coordinates
+    /// that can't be parsed.
     pub(crate) fn from_widget_config(
         view_name: &str,
         widget_name: &str,
         widget_config: &crate::config::Item,
-    ) -> Option<Self> {
-        if let Some(top) = &widget_config.top {
-            if let Some(left) = &widget_config.left {
-                let top = top.replace("%", "").parse::<f32>().expect(&format!(
-                    "Fafiled to parse top coordinate for {}",
-                    widget_name
-                )) * -1.;
-                let left = left.replace("%", "").parse::<f32>().expect(&format!(
-                    "Fafiled to parse left coordinate for {}",
-                    widget_name
-                ));
-
-                let id = egui::Id::new(format!("{}_{}", view_name, widget_name));
-
-                return Some(WidgetRenderSetting {
-                    top,
-                    left,
-                    id,
-                    label: widget_config.label.clone(),
-                    widget_name: widget_name.to_string(),
-                });
-            }
+    ) -> Result<Option<Self>, Vec<ConfigError>> {
+        let (Some(top), Some(left)) = (&widget_config.top, &widget_config.left) else {
+            return Ok(None);
+        };
+
+        let error =
+            |field: &str, kind| ConfigError::widget(view_name, widget_name, Some(field), kind);
+        match (parse_coordinate(top), parse_coordinate(left)) {
+            (Ok(top), Ok(left)) => Ok(Some(WidgetRenderSetting {
+                top: top * -1.,
+                left,
+                id: egui::Id::new(format!("{}_{}", view_name, widget_name)),
+                label: widget_config.label.clone(),
+                widget_name: widget_name.to_string(),
+            })),
+            (top, left) => Err([
+                top.err().map(|kind| error("top", kind)),
+                left.err().map(|kind| error("left", kind)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect()),
         }
-        None
+    }
+
+    /// Render setting for widgets that are not placed in the scene, e.g. for their fullscreen
+    /// view.
+    pub(crate) fn without_position(widget_name: &str, label: Option<String>) -> Self {
+        WidgetRenderSetting {
+            top: 0.,
+            left: 0.,
+            id: egui::Id::new(widget_name),
+            label,
+            widget_name: widget_name.to_string(),
+        }
+    }
+
+    /// Position of the widget in the 3D scene.
+    pub(crate) fn world_position(&self) -> Vec3 {
+        Vec3::new(self.left, WIDGET_HEIGHT, self.top)
     }
 }
 
@@ -104,24 +142,78 @@ pub(crate) enum SceneModification {
     Color(),
     Array(),
     Sun(),
+    Flow(),
+    Highlight(),
+    Position(),
+    Visibility(),
 }
 
 const MAX_ILLUMINATION: i32 = 400;
 
 /// Map string representation of scenen modification to corresponding enum
 impl SceneModification {
-    pub(crate) fn from_widget_config(string_representation: &str) -> Self {
+    pub(crate) fn from_widget_config(string_representation: &str) -> Result<Self, ConfigErrorKind> {
         match string_representation {
-            "Energy" => SceneModification::Energy(MAX_ILLUMINATION),
-            "Color" => SceneModification::Color(),
-            "Array" => SceneModification::Array(),
-            "Sun" => SceneModification::Sun(),
-            _ => {
-                panic!(
-                    "Unsupported scene configuration {} found in config",
-                    &string_representation
-                )
+            "Energy" => Ok(SceneModification::Energy(MAX_ILLUMINATION)),
+            "Color" => Ok(SceneModification::Color()),
+            "Array" => Ok(SceneModification::Array()),
+            "Sun" => Ok(SceneModification::Sun()),
+            "Flow" => Ok(SceneModification::Flow()),
+            "Highlight" => Ok(SceneModification::Highlight()),
+            "Position" => Ok(SceneModification::Position()),
+            "Visibility" => Ok(SceneModification::Visibility()),
+            _ => Err(ConfigErrorKind::UnknownSceneModification(
+                string_representation.to_string(),
+            )),
+        }
+    }
+
+    /// Modifications undoing this modification of the given entity, e.g. when its widget is
+    /// removed.
+    ///
+    /// Lights are switched off, tints, positions and visibility are restored and flows stopped.
+    /// The sun is left as it is.
+    pub(crate) fn reset(&self, entity_name: &EntityName) -> Vec<SceneModificationEvent> {
+        let entity_name = entity_name.to_string();
+        match self {
+            SceneModification::Energy(_) | SceneModification::Color() => {
+                vec![SceneModificationEvent::LightModification(
+                    LightModification {
+                        entity_name,
+                        illuminance_percentage: 0.,
+                    },
+                )]
+            }
+            SceneModification::Flow() => vec![SceneModificationEvent::EnergyFlowModification(
+                EnergyFlowModification {
+                    path_name: entity_name,
+                    points: None,
+                    power: 0.,
+                },
+            )],
+            SceneModification::Highlight() => {
+                vec![SceneModificationEvent::TintModification(TintModification {
+                    entity_name,
+                    color: None,
+                })]
+            }
+            SceneModification::Position() => {
+                vec![SceneModificationEvent::PositionModification(
+                    PositionModification {
+                        entity_name,
+                        position: None,
+                    },
+                )]
             }
+            SceneModification::Visibility() => {
+                vec![SceneModificationEvent::VisibilityModification(
+                    VisibilityModification {
+                        entity_name,
+                        visible: true,
+                    },
+                )]
+            }
+            SceneModification::Sun() | SceneModification::Array() => vec![],
         }
     }
 }
@@ -132,20 +224,34 @@ pub(crate) struct WidgetSceneModifications {
 
 impl WidgetSceneModifications {
     /// Parse scene modifications part of the widget configuration.
+    ///
+    /// Returns the errors of all unknown modifications.
     pub(crate) fn from_widget_config(
+        view_name: &str,
+        widget_name: &str,
         config: &std::collections::HashMap<String, Vec<String>>,
-    ) -> Self {
+    ) -> Result<Self, Vec<ConfigError>> {
         let mut hm = HashMap::new();
+        let mut errors = vec![];
         for (entity, modifications) in config {
-            hm.insert(
-                entity.to_string(),
-                modifications
-                    .iter()
-                    .map(|s| SceneModification::from_widget_config(s))
-                    .collect::<Vec<SceneModification>>(),
-            );
+            let mut parsed = vec![];
+            for (i, modification) in modifications.iter().enumerate() {
+                match SceneModification::from_widget_config(modification) {
+                    Ok(modification) => parsed.push(modification),
+                    Err(kind) => errors.push(ConfigError::widget(
+                        view_name,
+                        widget_name,
+                        Some(&format!("blenderItems.{}[{}]", entity, i)),
+                        kind,
+                    )),
+                }
+            }
+            hm.insert(entity.to_string(), parsed);
+        }
+        match errors.is_empty() {
+            true => Ok(Self { config: hm }),
+            false => Err(errors),
         }
-        Self { config: hm }
     }
 }
 
@@ -156,4 +262,41 @@ pub(crate) struct WidgetSettings {
     pub(crate) item_list: WidgetItemList,
     // Changes to the 3D scene
     pub(crate) scene_modifications: WidgetSceneModifications,
+    // Room the widget is in, if configured
+    pub(crate) room: Option<String>,
+    // Label of the widget, if configured
+    pub(crate) label: Option<String>,
+}
+
+#[test]
+fn test_invalid_widget_config() {
+    let widget_config = crate::config::Item {
+        top: Some("12%".to_string()),
+        left: Some("left".to_string()),
+        blender_items: [(
+            "Lamp".to_string(),
+            vec!["Energy".to_string(), "Glow".to_string()],
+        )]
+        .into(),
+        ..Default::default()
+    };
+    assert_eq!(
+        WidgetRenderSetting::from_widget_config("floorplan", "Kitchen", &widget_config).err(),
+        Some(vec![ConfigError {
+            path: "views.floorplan.items.Kitchen.left".to_string(),
+            kind: ConfigErrorKind::InvalidCoordinate("left".to_string()),
+        }])
+    );
+    assert_eq!(
+        WidgetSceneModifications::from_widget_config(
+            "floorplan",
+            "Kitchen",
+            &widget_config.blender_items
+        )
+        .err(),
+        Some(vec![ConfigError {
+            path: "views.floorplan.items.Kitchen.blenderItems.Lamp[1]".to_string(),
+            kind: ConfigErrorKind::UnknownSceneModification("Glow".to_string()),
+        }])
+    );
 }